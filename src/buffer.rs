@@ -0,0 +1,166 @@
+use std::{
+    fmt, io,
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use ropey::{Rope, RopeSlice};
+
+/// The revision the next change gets, counted across all buffers.
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(1);
+
+fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Buffer {
+    rope: Rope,
+    /// Changes with every edit. No two texts share one, so work done on a
+    /// revision can be kept until it changes. Empty buffers are all 0.
+    revision: u64,
+}
+
+impl Buffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_reader<R: io::Read>(reader: R) -> io::Result<Self> {
+        Ok(Self {
+            rope: Rope::from_reader(reader)?,
+            revision: next_revision(),
+        })
+    }
+
+    pub fn write_to<W: io::Write>(&self, writer: W) -> io::Result<()> {
+        self.rope.write_to(writer)
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.rope.len_chars()
+    }
+
+    /// Number of lines. Like vim, a line break at the end of the text ends
+    /// the last line rather than starting an empty one after it.
+    pub fn len_lines(&self) -> usize {
+        let lines = self.rope.len_lines();
+        if self.ends_with_line_break() {
+            lines - 1
+        } else {
+            lines
+        }
+    }
+
+    pub fn ends_with_line_break(&self) -> bool {
+        let length = self.len_chars();
+        length > 0 && self.rope.char(length - 1) == '\n'
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len_chars() == 0
+    }
+
+    pub fn char(&self, index: usize) -> Option<char> {
+        self.rope.get_char(index)
+    }
+
+    pub fn chars_at(&self, index: usize) -> ropey::iter::Chars<'_> {
+        self.rope.chars_at(index)
+    }
+
+    /// The line without its line ending.
+    pub fn line(&self, line: usize) -> Option<RopeSlice<'_>> {
+        if line >= self.len_lines() {
+            return None;
+        }
+        let slice = self.rope.get_line(line)?;
+        Some(slice.slice(..Self::trimmed_len(slice)))
+    }
+
+    /// Number of chars in the line, not counting its line ending.
+    pub fn line_len(&self, line: usize) -> Option<usize> {
+        self.line(line).map(|line| line.len_chars())
+    }
+
+    pub fn line_to_char(&self, line: usize) -> usize {
+        self.rope.line_to_char(line.min(self.len_lines()))
+    }
+
+    /// The line `index` is on. The end of text that ends with a line break
+    /// counts as the last line.
+    pub fn char_to_line(&self, index: usize) -> usize {
+        let line = self.rope.char_to_line(index.min(self.len_chars()));
+        line.min(self.len_lines() - 1)
+    }
+
+    pub fn char_to_byte(&self, index: usize) -> usize {
+        self.rope.char_to_byte(index.min(self.len_chars()))
+    }
+
+    pub fn byte_to_char(&self, index: usize) -> usize {
+        self.rope.byte_to_char(index.min(self.rope.len_bytes()))
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> RopeSlice<'_> {
+        self.rope.slice(range)
+    }
+
+    pub fn insert(&mut self, index: usize, text: &str) {
+        self.rope.insert(index, text);
+        self.revision = next_revision();
+    }
+
+    pub fn insert_char(&mut self, index: usize, char: char) {
+        self.rope.insert_char(index, char);
+        self.revision = next_revision();
+    }
+
+    pub fn remove(&mut self, range: Range<usize>) {
+        self.rope.remove(range);
+        self.revision = next_revision();
+    }
+
+    /// FNV-1a hash of the text, stable between runs unlike `DefaultHasher`.
+    pub fn content_hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in self.rope.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+
+    fn trimmed_len(line: RopeSlice) -> usize {
+        let mut length = line.len_chars();
+        if length > 0 && line.char(length - 1) == '\n' {
+            length -= 1;
+        }
+        if length > 0 && line.char(length - 1) == '\r' {
+            length -= 1;
+        }
+        length
+    }
+}
+
+impl From<&str> for Buffer {
+    fn from(text: &str) -> Self {
+        Self {
+            rope: Rope::from_str(text),
+            revision: next_revision(),
+        }
+    }
+}
+
+impl fmt::Display for Buffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.rope.chunks() {
+            f.write_str(chunk)?;
+        }
+        Ok(())
+    }
+}
@@ -1,23 +1,179 @@
+use std::env;
+
 use crossterm::event::KeyCode;
 
 use crate::{
-    jim::JimProperties,
-    mapping::{Command, Mapping, ToMapping},
+    ex::{ExCommand, LineRange},
+    file,
+    jim::{Jim, JimProperties},
+    mapping::{Command, MapContext, Mapping, ToMapping},
     mode::Mode,
+    motion::{self, MotionKind},
+    operator::Operator,
+    register::Registers,
+    selection::Selection,
+    substitute::Substitute,
+    window::{ScreenLine, Side, Split},
 };
 
 pub fn nmaps() -> Vec<Mapping> {
     vec![
         ":".to_mapping(command_mode, false),
         "i".to_mapping(i, false),
-        "h".to_mapping(h, false),
-        "j".to_mapping(j, false),
-        "k".to_mapping(k, false),
-        "l".to_mapping(l, false),
-        "f".to_mapping(f, true),
-        "t".to_mapping(t, true),
+        "h".to_motion(h, MotionKind::Exclusive, false),
+        "j".to_motion(j, MotionKind::Linewise, false),
+        "k".to_motion(k, MotionKind::Linewise, false),
+        "l".to_motion(l, MotionKind::Exclusive, false),
+        "f".to_motion(f, MotionKind::Inclusive, true),
+        "t".to_motion(t, MotionKind::Inclusive, true),
+        "w".to_motion(w, MotionKind::Exclusive, false),
+        "e".to_motion(e, MotionKind::Inclusive, false),
+        "b".to_motion(b, MotionKind::Exclusive, false),
+        "$".to_motion(dollar, MotionKind::Inclusive, false),
+        "0".to_motion(zero, MotionKind::Exclusive, false),
+        "gg".to_motion(gg, MotionKind::Linewise, false),
+        "G".to_motion(shift_g, MotionKind::Linewise, false),
+        "n".to_motion(n, MotionKind::Exclusive, false),
+        "N".to_motion(shift_n, MotionKind::Exclusive, false),
+        "*".to_motion(star, MotionKind::Exclusive, false),
+        "#".to_motion(hash, MotionKind::Exclusive, false),
+        "/".to_mapping(|jim, context| jim.begin_search(false, context), false),
+        "?".to_mapping(|jim, context| jim.begin_search(true, context), false),
+        "d".to_mapping(d, false),
+        "c".to_mapping(c, false),
+        "y".to_mapping(y, false),
+        ">".to_mapping(greater, false),
+        "<lt>".to_mapping(less, false),
+        "g~".to_mapping(g_tilde, false),
+        "p".to_mapping(p, false),
+        "P".to_mapping(shift_p, false),
         "a".to_mapping(a, false),
         "A".to_mapping(shift_a, false),
+        "u".to_mapping(u, false),
+        "<C-r>".to_mapping(ctrl_r, false),
+        "g-".to_mapping(g_minus, false),
+        "g+".to_mapping(g_plus, false),
+        "v".to_mapping(v, false),
+        "V".to_mapping(shift_v, false),
+        "<C-v>".to_mapping(ctrl_v, false),
+        "<C-d>".to_mapping(ctrl_d, false),
+        "<C-u>".to_mapping(ctrl_u, false),
+        "<C-f>".to_mapping(
+            |jim, context| jim.scroll_pages(context.count() as isize),
+            false,
+        ),
+        "<C-b>".to_mapping(
+            |jim, context| jim.scroll_pages(-(context.count() as isize)),
+            false,
+        ),
+        "&".to_mapping(ampersand, false),
+        "ZZ".to_mapping(shift_z_z, false),
+        "ZQ".to_mapping(shift_z_q, false),
+        "zt".to_mapping(|jim, _| jim.scroll_cursor_to(ScreenLine::Top), false),
+        "zz".to_mapping(|jim, _| jim.scroll_cursor_to(ScreenLine::Middle), false),
+        "zb".to_mapping(|jim, _| jim.scroll_cursor_to(ScreenLine::Bottom), false),
+        "<CR>".to_mapping(enter, false),
+        "<C-^>".to_mapping(ctrl_caret, false),
+        "<C-6>".to_mapping(ctrl_caret, false),
+        "<C-w>s".to_mapping(|jim, _| jim.split_window(Split::Horizontal), false),
+        "<C-w>v".to_mapping(|jim, _| jim.split_window(Split::Vertical), false),
+        "<C-w>c".to_mapping(ctrl_w_c, false),
+        "<C-w>q".to_mapping(ctrl_w_q, false),
+        "<C-w>o".to_mapping(|jim, _| jim.windows.only(), false),
+        "<C-w>w".to_mapping(ctrl_w_w, false),
+        "<C-w><C-w>".to_mapping(ctrl_w_w, false),
+        "<C-w>h".to_mapping(
+            |jim, context| jim.focus_neighbour(Side::Left, context.count()),
+            false,
+        ),
+        "<C-w>j".to_mapping(
+            |jim, context| jim.focus_neighbour(Side::Below, context.count()),
+            false,
+        ),
+        "<C-w>k".to_mapping(
+            |jim, context| jim.focus_neighbour(Side::Above, context.count()),
+            false,
+        ),
+        "<C-w>l".to_mapping(
+            |jim, context| jim.focus_neighbour(Side::Right, context.count()),
+            false,
+        ),
+        "<C-w>+".to_mapping(
+            |jim, context| resize(jim, Split::Horizontal, context, 1),
+            false,
+        ),
+        "<C-w>-".to_mapping(
+            |jim, context| resize(jim, Split::Horizontal, context, -1),
+            false,
+        ),
+        "<C-w>>".to_mapping(
+            |jim, context| resize(jim, Split::Vertical, context, 1),
+            false,
+        ),
+        "<C-w><lt>".to_mapping(
+            |jim, context| resize(jim, Split::Vertical, context, -1),
+            false,
+        ),
+        "<C-w>=".to_mapping(|jim, _| jim.windows.equalize(), false),
+    ]
+}
+
+pub fn vmaps() -> Vec<Mapping> {
+    vec![
+        "<Esc>".to_mapping(visual_escape, false),
+        "v".to_mapping(v, false),
+        "V".to_mapping(shift_v, false),
+        "<C-v>".to_mapping(ctrl_v, false),
+        "o".to_mapping(visual_o, false),
+        "d".to_mapping(visual_d, false),
+        "x".to_mapping(visual_d, false),
+        "y".to_mapping(visual_y, false),
+        "c".to_mapping(visual_c, false),
+        ">".to_mapping(visual_greater, false),
+        "<lt>".to_mapping(visual_less, false),
+        "~".to_mapping(visual_tilde, false),
+    ]
+}
+
+/// Text objects, used after an operator and in visual mode.
+pub fn omaps() -> Vec<Mapping> {
+    vec![
+        "d".to_mapping(current_line, false),
+        "/".to_mapping(|jim, context| jim.begin_search(false, context), false),
+        "?".to_mapping(|jim, context| jim.begin_search(true, context), false),
+        "c".to_mapping(current_line, false),
+        "y".to_mapping(current_line, false),
+        ">".to_mapping(current_line, false),
+        "<lt>".to_mapping(current_line, false),
+        "~".to_mapping(current_line, false),
+        "iw".to_mapping(|jim, _| word(jim, false), false),
+        "aw".to_mapping(|jim, _| word(jim, true), false),
+        "i(".to_mapping(|jim, _| pair(jim, '(', ')', false), false),
+        "a(".to_mapping(|jim, _| pair(jim, '(', ')', true), false),
+        "i)".to_mapping(|jim, _| pair(jim, '(', ')', false), false),
+        "a)".to_mapping(|jim, _| pair(jim, '(', ')', true), false),
+        "ib".to_mapping(|jim, _| pair(jim, '(', ')', false), false),
+        "ab".to_mapping(|jim, _| pair(jim, '(', ')', true), false),
+        "i{".to_mapping(|jim, _| pair(jim, '{', '}', false), false),
+        "a{".to_mapping(|jim, _| pair(jim, '{', '}', true), false),
+        "i}".to_mapping(|jim, _| pair(jim, '{', '}', false), false),
+        "a}".to_mapping(|jim, _| pair(jim, '{', '}', true), false),
+        "iB".to_mapping(|jim, _| pair(jim, '{', '}', false), false),
+        "aB".to_mapping(|jim, _| pair(jim, '{', '}', true), false),
+        "i[".to_mapping(|jim, _| pair(jim, '[', ']', false), false),
+        "a[".to_mapping(|jim, _| pair(jim, '[', ']', true), false),
+        "i]".to_mapping(|jim, _| pair(jim, '[', ']', false), false),
+        "a]".to_mapping(|jim, _| pair(jim, '[', ']', true), false),
+        "i<lt>".to_mapping(|jim, _| pair(jim, '<', '>', false), false),
+        "a<lt>".to_mapping(|jim, _| pair(jim, '<', '>', true), false),
+        "i>".to_mapping(|jim, _| pair(jim, '<', '>', false), false),
+        "a>".to_mapping(|jim, _| pair(jim, '<', '>', true), false),
+        "i\"".to_mapping(|jim, _| quote(jim, '"', false), false),
+        "a\"".to_mapping(|jim, _| quote(jim, '"', true), false),
+        "i'".to_mapping(|jim, _| quote(jim, '\'', false), false),
+        "a'".to_mapping(|jim, _| quote(jim, '\'', true), false),
+        "i`".to_mapping(|jim, _| quote(jim, '`', false), false),
+        "a`".to_mapping(|jim, _| quote(jim, '`', true), false),
     ]
 }
 
@@ -27,87 +183,680 @@ pub fn imaps() -> Vec<Mapping> {
 
 pub fn cmaps() -> Vec<Command> {
     vec![
-        Command::new("q", quit), 
-        Command::new("quit", quit), 
-        Command::new("w", save),
-        Command::new("write", save),
+        Command::new("q[uit]", |jim, command| quit(jim, command.bang)),
+        Command::new("qa[ll]", |jim, command| quit_all(jim, command.bang)),
+        Command::new("quita[ll]", |jim, command| quit_all(jim, command.bang)),
+        Command::new("w[rite]", save),
+        Command::new("wa[ll]", |jim, command| save_all(jim, command.bang)),
         Command::new("wq", save_and_quit),
+        Command::new("x[it]", exit),
+        Command::new("exi[t]", exit),
+        Command::new("e[dit]", edit),
+        Command::new("bn[ext]", |jim, _| cycle_buffers(jim, true)),
+        Command::new("bp[revious]", |jim, _| cycle_buffers(jim, false)),
+        Command::new("b[uffer]", buffer),
+        Command::new("bd[elete]", buffer_delete),
+        Command::new("ls", list_buffers),
+        Command::new("buffers", list_buffers),
+        Command::new("files", list_buffers),
+        Command::new("sp[lit]", |jim, _| split(jim, Split::Horizontal)),
+        Command::new("vs[plit]", |jim, _| split(jim, Split::Vertical)),
+        Command::new("clo[se]", |jim, _| close_window(jim)),
+        Command::new("on[ly]", |jim, _| only(jim)),
+        Command::new("s[ubstitute]", substitute).with_range(),
+        Command::new("&", |jim, command| {
+            repeat_substitute(jim, command.range, &command.arguments)
+        })
+        .with_range(),
+        Command::editor("g[lobal]", |jim, command| jim.global(command, false)).with_range(),
+        Command::editor("v[global]", |jim, command| jim.global(command, true)).with_range(),
+        Command::editor("norm[al]", Jim::normal_command).with_range(),
+        Command::new("d[elete]", |jim, command| {
+            lines(jim, command, Operator::Delete)
+        })
+        .with_range(),
+        Command::new("y[ank]", |jim, command| lines(jim, command, Operator::Yank)).with_range(),
+        Command::new("reg[isters]", registers),
+        Command::new("di[splay]", registers),
+        Command::new("mes[sages]", messages),
+        Command::new("checkt[ime]", check_files),
+        Command::new("se[t]", set),
     ]
 }
 
-fn quit(jim: &mut JimProperties) {
-    jim.quitting = true;
+/// Closes the current window, quitting once it's the last one. Changes
+/// that haven't been written keep jim open unless `force` is set.
+fn quit(jim: &mut JimProperties, force: bool) -> Result<(), String> {
+    if jim.close_window() {
+        return Ok(());
+    }
+    quit_all(jim, force)
+}
+
+fn quit_all(jim: &mut JimProperties, force: bool) -> Result<(), String> {
+    match jim.modified_buffer() {
+        Some(index) if !force => Err(format!(
+            "No write since last change for buffer \"{}\" (add ! to override)",
+            jim.buffers[index].name()
+        )),
+        _ => {
+            jim.quitting = true;
+            Ok(())
+        }
+    }
+}
+
+fn split(jim: &mut JimProperties, split: Split) -> Result<(), String> {
+    jim.split_window(split);
+    Ok(())
+}
+
+fn close_window(jim: &mut JimProperties) -> Result<(), String> {
+    match jim.close_window() {
+        true => Ok(()),
+        false => Err("Cannot close last window".to_string()),
+    }
+}
+
+fn only(jim: &mut JimProperties) -> Result<(), String> {
+    jim.windows.only();
+    Ok(())
+}
+
+fn ampersand(jim: &mut JimProperties, _: MapContext) {
+    let repeated = repeat_substitute(jim, None, "");
+    jim.report(repeated);
+}
+
+fn ctrl_w_q(jim: &mut JimProperties, _: MapContext) {
+    let quit = quit(jim, false);
+    jim.report(quit);
+}
+
+fn ctrl_w_c(jim: &mut JimProperties, _: MapContext) {
+    let closed = close_window(jim);
+    jim.report(closed);
+}
+
+fn shift_z_z(jim: &mut JimProperties, _: MapContext) {
+    let exit = exit(jim, &ExCommand::default());
+    jim.report(exit);
+}
+
+fn shift_z_q(jim: &mut JimProperties, _: MapContext) {
+    let quit = quit(jim, true);
+    jim.report(quit);
+}
+
+fn ctrl_w_w(jim: &mut JimProperties, _: MapContext) {
+    let next = jim.windows.next();
+    jim.focus_window(next);
+}
+
+/// Half a window, or `count` lines.
+fn scroll_amount(jim: &JimProperties, context: MapContext) -> isize {
+    let height = jim.windows.text_area(jim.windows.current()).height;
+    context.count.unwrap_or((height as usize / 2).max(1)) as isize
+}
+
+fn ctrl_d(jim: &mut JimProperties, context: MapContext) {
+    jim.scroll_lines(scroll_amount(jim, context));
+}
+
+fn ctrl_u(jim: &mut JimProperties, context: MapContext) {
+    jim.scroll_lines(-scroll_amount(jim, context));
+}
+
+fn resize(jim: &mut JimProperties, split: Split, context: MapContext, direction: isize) {
+    jim.windows
+        .resize(split, direction * context.count() as isize);
+}
+
+/// Opens a file, or reads the current buffer's file again without one.
+/// Reloading throws changes away so it takes `:e!` unless there are none.
+fn edit(jim: &mut JimProperties, command: &ExCommand) -> Result<(), String> {
+    let path = &command.arguments;
+    if path.is_empty() {
+        if !command.bang && jim.get_current_buffer().modified {
+            return Err("No write since last change (add ! to override)".to_string());
+        }
+        return jim
+            .reload_buffer()
+            .map_err(|error| format!("\"{}\" {error}", jim.get_current_buffer().name()));
+    }
+    env::current_dir()
+        .and_then(|directory| jim.open_file(&directory.join(path)))
+        .map_err(|error| format!("\"{path}\" {error}"))
+}
+
+fn cycle_buffers(jim: &mut JimProperties, forward: bool) -> Result<(), String> {
+    let count = jim.buffers.len();
+    let current = jim.recent_buffers[0];
+    let next = if forward {
+        (current + 1) % count
+    } else {
+        (current + count - 1) % count
+    };
+    jim.switch_buffer(next);
+    Ok(())
+}
+
+fn buffer(jim: &mut JimProperties, command: &ExCommand) -> Result<(), String> {
+    if command.arguments.is_empty() {
+        return Ok(());
+    }
+    let index = jim.find_buffer(&command.arguments)?;
+    jim.switch_buffer(index);
+    Ok(())
+}
+
+fn buffer_delete(jim: &mut JimProperties, command: &ExCommand) -> Result<(), String> {
+    let index = match command.arguments.as_str() {
+        "" => jim.recent_buffers[0],
+        name => jim.find_buffer(name)?,
+    };
+    if jim.buffers[index].modified && !command.bang {
+        return Err(format!(
+            "No write since last change for buffer \"{}\" (add ! to override)",
+            jim.buffers[index].name()
+        ));
+    }
+    jim.delete_buffer(index)
 }
 
-fn save(jim: &mut JimProperties) {
-    jim.get_current_buffer().save().unwrap();
+fn list_buffers(jim: &mut JimProperties, _: &ExCommand) -> Result<(), String> {
+    jim.list_buffers();
+    Ok(())
 }
 
-fn save_and_quit(jim: &mut JimProperties) {
-    save(jim);
-    quit(jim);
+/// `:[range]d [x] [count]` and `:y`, applies `operator` to whole lines. A
+/// count takes that many lines from the end of the range.
+fn lines(jim: &mut JimProperties, command: &ExCommand, operator: Operator) -> Result<(), String> {
+    let line_count = jim.get_current_buffer_contents().len_lines();
+    let (mut first, mut last) = jim.command_lines(command.range)?;
+
+    let mut arguments = command.arguments.as_str();
+    let register = match arguments.chars().next() {
+        Some(name) if !name.is_ascii_digit() => {
+            if !Registers::is_valid_name(name) {
+                return Err(format!("Invalid register name: {name}"));
+            }
+            arguments = arguments[name.len_utf8()..].trim_start();
+            Some(name)
+        }
+        _ => None,
+    };
+    if !arguments.is_empty() {
+        let count: usize = arguments
+            .parse()
+            .map_err(|_| format!("Trailing characters: {arguments}"))?;
+        first = last;
+        last = (last + count.max(1) - 1).min(line_count - 1);
+    }
+
+    let selection = Selection::lines(first, last, jim.get_current_buffer());
+    operator.apply(jim, selection, register);
+    Ok(())
+}
+
+/// Opens the entry under the cursor in an explorer, elsewhere moves down
+/// to the start of a later line.
+fn enter(jim: &mut JimProperties, context: MapContext) {
+    if jim.get_current_buffer().explorer.is_some() {
+        jim.open_explorer_entry();
+    } else {
+        jim.move_cursor_down(context.count());
+        jim.move_cursor_full_left();
+    }
 }
 
-fn command_mode(jim: &mut JimProperties) {
+fn ctrl_caret(jim: &mut JimProperties, context: MapContext) {
+    let index = match context.count {
+        Some(number) => jim.find_buffer(&number.to_string()),
+        None => jim
+            .alternate_buffer()
+            .ok_or_else(|| "No alternate file".to_string()),
+    };
+    let switched = index.map(|index| jim.switch_buffer(index));
+    jim.report(switched);
+}
+
+/// `:s/pattern/replacement/flags`. Without a pattern it repeats the last
+/// substitution with the flags given, and an empty pattern is the last one
+/// searched for.
+fn substitute(jim: &mut JimProperties, command: &ExCommand) -> Result<(), String> {
+    let arguments = command.arguments.as_str();
+    if arguments.chars().all(|char| "&cgiI".contains(char)) {
+        return repeat_substitute(jim, command.range, arguments);
+    }
+    let mut substitute = Substitute::parse(arguments)?;
+    if substitute.pattern.is_empty() {
+        substitute.pattern = jim
+            .search
+            .pattern
+            .clone()
+            .ok_or("No previous regular expression")?;
+    }
+    let (first, last) = jim.command_lines(command.range)?;
+    jim.substitute(substitute, first, last)
+}
+
+/// `:&` and `&`, the last substitution again with new flags, or the same
+/// ones when `flags` starts with `&`.
+fn repeat_substitute(
+    jim: &mut JimProperties,
+    range: Option<LineRange>,
+    flags: &str,
+) -> Result<(), String> {
+    let mut substitute = jim
+        .last_substitute
+        .clone()
+        .ok_or("No previous substitute regular expression")?;
+    substitute.flags.apply(flags)?;
+    let (first, last) = jim.command_lines(range)?;
+    jim.substitute(substitute, first, last)
+}
+
+/// Writes the current buffer, or a copy of it to the file named in the
+/// arguments. `++p` creates missing parent directories. In an explorer it
+/// asks to apply the edits instead.
+fn save(jim: &mut JimProperties, command: &ExCommand) -> Result<(), String> {
+    if jim.get_current_buffer().explorer.is_some() {
+        jim.confirm_explorer_changes();
+        return Ok(());
+    }
+    let (flags, names): (Vec<&str>, Vec<&str>) = command
+        .arguments
+        .split_whitespace()
+        .partition(|argument| argument.starts_with("++"));
+    let create_parents = flags.contains(&"++p");
+    let buffer = &mut jim.buffers[jim.recent_buffers[0]];
+    if !names.is_empty() {
+        let name = names.join(" ");
+        let path = env::current_dir()
+            .map(|directory| file::normalize_path(&directory.join(&name)))
+            .map_err(|error| error.to_string())?;
+        if path != buffer.path {
+            return buffer
+                .write_copy(&path, create_parents, command.bang)
+                .map_err(|error| format!("\"{name}\" {error}"));
+        }
+    }
+    buffer
+        .save(create_parents, command.bang, &jim.options)
+        .map_err(|error| format!("\"{}\" {error}", buffer.name()))
+}
+
+/// Writes every modified buffer, stopping at the first that can't be.
+/// Directory listings are left alone since they need confirming.
+fn save_all(jim: &mut JimProperties, force: bool) -> Result<(), String> {
+    for buffer in &mut jim.buffers {
+        if !buffer.modified || buffer.explorer.is_some() || buffer.scratch {
+            continue;
+        }
+        buffer
+            .save(false, force, &jim.options)
+            .map_err(|error| format!("\"{}\" {error}", buffer.name()))?;
+    }
+    Ok(())
+}
+
+/// Quits once the current buffer has nothing left to write, an explorer
+/// waiting for its changes to be confirmed stays open.
+fn quit_if_saved(jim: &mut JimProperties, force: bool) -> Result<(), String> {
+    if jim.get_current_buffer().modified {
+        return Ok(());
+    }
+    quit(jim, force)
+}
+
+fn save_and_quit(jim: &mut JimProperties, command: &ExCommand) -> Result<(), String> {
+    save(jim, command)?;
+    quit_if_saved(jim, command.bang)
+}
+
+/// Like `:wq`, but only writes when there are changes.
+fn exit(jim: &mut JimProperties, command: &ExCommand) -> Result<(), String> {
+    if jim.get_current_buffer().modified {
+        save(jim, command)?;
+    }
+    quit_if_saved(jim, command.bang)
+}
+
+fn set(jim: &mut JimProperties, command: &ExCommand) -> Result<(), String> {
+    command
+        .arguments
+        .split_whitespace()
+        .try_for_each(|argument| jim.options.set(argument))
+}
+
+fn registers(jim: &mut JimProperties, _: &ExCommand) -> Result<(), String> {
+    jim.show_registers();
+    Ok(())
+}
+
+/// Opens the message history, `:messages clear` forgets it.
+fn messages(jim: &mut JimProperties, command: &ExCommand) -> Result<(), String> {
+    match command.arguments.as_str() {
+        "" => jim.open_messages(),
+        "clear" => jim.messages.clear(),
+        arguments => return Err(format!("Invalid argument: {arguments}")),
+    }
+    Ok(())
+}
+
+fn check_files(jim: &mut JimProperties, _: &ExCommand) -> Result<(), String> {
+    jim.check_files();
+    Ok(())
+}
+
+fn command_mode(jim: &mut JimProperties, _: MapContext) {
     jim.mode = Mode::Command;
 }
 
-fn i(jim: &mut JimProperties) {
+fn i(jim: &mut JimProperties, _: MapContext) {
     jim.mode = Mode::Insert;
 }
 
-fn h(jim: &mut JimProperties) {
-    jim.move_cursor_left(1);
+fn h(jim: &mut JimProperties, context: MapContext) {
+    jim.move_cursor_left(context.count());
 }
 
-fn j(jim: &mut JimProperties) {
-    jim.move_cursor_down(1);
+fn j(jim: &mut JimProperties, context: MapContext) {
+    jim.move_cursor_down(context.count());
 }
 
-fn k(jim: &mut JimProperties) {
-    jim.move_cursor_up(1);
+fn k(jim: &mut JimProperties, context: MapContext) {
+    jim.move_cursor_up(context.count());
 }
 
-fn l(jim: &mut JimProperties) {
-    jim.move_cursor_right(1);
+fn l(jim: &mut JimProperties, context: MapContext) {
+    // After an operator `l` may go past the last char so `dl` can take it.
+    if jim.pending_operator.is_some() {
+        jim.force_move_cursor_right(context.count());
+    } else {
+        jim.move_cursor_right(context.count());
+    }
 }
 
-fn f(jim: &mut JimProperties) {
-    if let Some(distance) = distance_to_next_button(jim) {
+fn f(jim: &mut JimProperties, context: MapContext) {
+    if let Some(distance) = distance_to_next_button(jim, context.count()) {
         jim.move_cursor_right(distance + 1);
     }
 }
 
-fn t(jim: &mut JimProperties) {
-    if let Some(distance) = distance_to_next_button(jim) {
+fn t(jim: &mut JimProperties, context: MapContext) {
+    if let Some(distance) = distance_to_next_button(jim, context.count()) {
         jim.move_cursor_right(distance);
     }
 }
 
-fn a(jim: &mut JimProperties) {
+fn n(jim: &mut JimProperties, context: MapContext) {
+    let found = jim.search_next(context.count(), false);
+    jim.report(found);
+}
+
+fn shift_n(jim: &mut JimProperties, context: MapContext) {
+    let found = jim.search_next(context.count(), true);
+    jim.report(found);
+}
+
+fn star(jim: &mut JimProperties, context: MapContext) {
+    let found = jim.search_word(false, context.count());
+    jim.report(found);
+}
+
+fn hash(jim: &mut JimProperties, context: MapContext) {
+    let found = jim.search_word(true, context.count());
+    jim.report(found);
+}
+
+fn w(jim: &mut JimProperties, context: MapContext) {
+    let contents = jim.get_current_buffer_contents();
+    let on_word = contents
+        .char(jim.cursor.index)
+        .is_some_and(|char| !char.is_whitespace());
+    // Like vim, `cw` on a word is `ce` and leaves the blanks after it, but
+    // stops at the end of the word the cursor is already at the end of.
+    if jim.pending_operator == Some(Operator::Change) && on_word {
+        let mut index = motion::current_word_end(contents, jim.cursor.index);
+        for _ in 1..context.count() {
+            index = motion::word_end(contents, index);
+        }
+        jim.set_cursor_index(index + 1);
+        return;
+    }
+
+    let mut index = jim.cursor.index;
+    for _ in 0..context.count() {
+        index = motion::next_word_start(jim.get_current_buffer_contents(), index);
+    }
+    if jim.pending_operator.is_none() {
+        index = index.min(
+            jim.get_current_buffer_contents()
+                .len_chars()
+                .saturating_sub(1),
+        );
+    }
+    jim.set_cursor_index(index);
+}
+
+fn e(jim: &mut JimProperties, context: MapContext) {
+    let mut index = jim.cursor.index;
+    for _ in 0..context.count() {
+        index = motion::word_end(jim.get_current_buffer_contents(), index);
+    }
+    jim.set_cursor_index(index);
+}
+
+fn b(jim: &mut JimProperties, context: MapContext) {
+    let mut index = jim.cursor.index;
+    for _ in 0..context.count() {
+        index = motion::previous_word_start(jim.get_current_buffer_contents(), index);
+    }
+    jim.set_cursor_index(index);
+}
+
+fn dollar(jim: &mut JimProperties, context: MapContext) {
+    jim.move_cursor_down(context.count() - 1);
+    jim.move_cursor_full_right();
+    jim.move_cursor_left(1);
+}
+
+fn zero(jim: &mut JimProperties, _: MapContext) {
+    jim.move_cursor_full_left();
+}
+
+fn gg(jim: &mut JimProperties, context: MapContext) {
+    go_to_line(jim, context.count.unwrap_or(1));
+}
+
+fn shift_g(jim: &mut JimProperties, context: MapContext) {
+    let last_line = jim.get_current_buffer_contents().len_lines();
+    go_to_line(jim, context.count.unwrap_or(last_line));
+}
+
+fn go_to_line(jim: &mut JimProperties, line: usize) {
+    let contents = jim.get_current_buffer_contents();
+    let line = line.clamp(1, contents.len_lines()) - 1;
+    jim.set_cursor_index(contents.line_to_char(line));
+}
+
+fn d(jim: &mut JimProperties, context: MapContext) {
+    jim.begin_operator(Operator::Delete, context);
+}
+
+fn c(jim: &mut JimProperties, context: MapContext) {
+    jim.begin_operator(Operator::Change, context);
+}
+
+fn y(jim: &mut JimProperties, context: MapContext) {
+    jim.begin_operator(Operator::Yank, context);
+}
+
+fn greater(jim: &mut JimProperties, context: MapContext) {
+    jim.begin_operator(Operator::Indent, context);
+}
+
+fn less(jim: &mut JimProperties, context: MapContext) {
+    jim.begin_operator(Operator::Outdent, context);
+}
+
+fn g_tilde(jim: &mut JimProperties, context: MapContext) {
+    jim.begin_operator(Operator::ToggleCase, context);
+}
+
+fn p(jim: &mut JimProperties, context: MapContext) {
+    jim.put(context.register, false, context.count());
+}
+
+fn shift_p(jim: &mut JimProperties, context: MapContext) {
+    jim.put(context.register, true, context.count());
+}
+
+fn current_line(jim: &mut JimProperties, context: MapContext) {
+    let Some(KeyCode::Char(pressed)) = jim.buttons_pressed.last().map(|key| key.code) else {
+        return;
+    };
+    if jim.pending_operator.map(Operator::line_key) == Some(pressed) {
+        jim.move_cursor_down(context.count() - 1);
+        jim.apply_operator_to_motion(MotionKind::Linewise);
+    }
+}
+
+fn word(jim: &mut JimProperties, around: bool) {
+    let range = motion::word_object(jim.get_current_buffer_contents(), jim.cursor.index, around);
+    jim.select_text_object(range);
+}
+
+fn pair(jim: &mut JimProperties, open: char, close: char, around: bool) {
+    let contents = jim.get_current_buffer_contents();
+    if let Some(range) = motion::pair_object(contents, jim.cursor.index, open, close, around) {
+        jim.select_text_object(range);
+    }
+}
+
+fn quote(jim: &mut JimProperties, quote: char, around: bool) {
+    let contents = jim.get_current_buffer_contents();
+    if let Some(range) = motion::quote_object(contents, jim.cursor.index, quote, around) {
+        jim.select_text_object(range);
+    }
+}
+
+fn a(jim: &mut JimProperties, _: MapContext) {
     jim.force_move_cursor_right(1);
     jim.mode = Mode::Insert;
 }
 
-fn shift_a(jim: &mut JimProperties) {
+fn shift_a(jim: &mut JimProperties, _: MapContext) {
     jim.move_cursor_full_right();
     jim.mode = Mode::Insert;
 }
 
-fn distance_to_next_button(jim: &mut JimProperties) -> Option<usize> {
+fn u(jim: &mut JimProperties, context: MapContext) {
+    for _ in 0..context.count() {
+        jim.undo();
+    }
+}
+
+fn ctrl_r(jim: &mut JimProperties, context: MapContext) {
+    for _ in 0..context.count() {
+        jim.redo();
+    }
+}
+
+fn g_minus(jim: &mut JimProperties, context: MapContext) {
+    for _ in 0..context.count() {
+        jim.undo_earlier();
+    }
+}
+
+fn g_plus(jim: &mut JimProperties, context: MapContext) {
+    for _ in 0..context.count() {
+        jim.undo_later();
+    }
+}
+
+fn v(jim: &mut JimProperties, _: MapContext) {
+    enter_visual_mode(jim, Mode::Visual);
+}
+
+fn shift_v(jim: &mut JimProperties, _: MapContext) {
+    enter_visual_mode(jim, Mode::VisualLine);
+}
+
+fn ctrl_v(jim: &mut JimProperties, _: MapContext) {
+    enter_visual_mode(jim, Mode::VisualBlock);
+}
+
+fn enter_visual_mode(jim: &mut JimProperties, mode: Mode) {
+    if jim.mode == mode {
+        jim.mode = Mode::Normal;
+        return;
+    }
+    if !jim.mode.is_visual() {
+        jim.visual_anchor = jim.cursor;
+    }
+    jim.mode = mode;
+}
+
+fn visual_escape(jim: &mut JimProperties, _: MapContext) {
+    jim.mode = Mode::Normal;
+}
+
+fn visual_o(jim: &mut JimProperties, _: MapContext) {
+    std::mem::swap(&mut jim.cursor, &mut jim.visual_anchor);
+}
+
+fn visual_d(jim: &mut JimProperties, context: MapContext) {
+    apply_to_selection(jim, Operator::Delete, context);
+}
+
+fn visual_y(jim: &mut JimProperties, context: MapContext) {
+    apply_to_selection(jim, Operator::Yank, context);
+}
+
+fn visual_c(jim: &mut JimProperties, context: MapContext) {
+    apply_to_selection(jim, Operator::Change, context);
+}
+
+fn visual_greater(jim: &mut JimProperties, context: MapContext) {
+    apply_to_selection(jim, Operator::Indent, context);
+}
+
+fn visual_less(jim: &mut JimProperties, context: MapContext) {
+    apply_to_selection(jim, Operator::Outdent, context);
+}
+
+fn visual_tilde(jim: &mut JimProperties, context: MapContext) {
+    apply_to_selection(jim, Operator::ToggleCase, context);
+}
+
+fn apply_to_selection(jim: &mut JimProperties, operator: Operator, context: MapContext) {
+    if let Some(selection) = jim.selection() {
+        operator.apply(jim, selection, context.register);
+    }
+}
+
+fn distance_to_next_button(jim: &mut JimProperties, count: usize) -> Option<usize> {
     let chars: Vec<char> = jim
         .current_line()
         .chars()
         .skip(jim.cursor.xy_pos.x + 1)
         .collect();
-    let target = match jim.buttons_pressed.last().unwrap() {
+    let target = match jim.buttons_pressed.last()?.code {
         KeyCode::Char(char) => char,
-        _ => todo!(),
+        _ => return None,
     };
 
-    return find_char_position(&chars, *target);
+    find_char_position(&chars, target, count)
 }
 
-fn find_char_position(chars: &[char], target: char) -> Option<usize> {
-    chars.iter().position(|&c| c == target)
+fn find_char_position(chars: &[char], target: char, count: usize) -> Option<usize> {
+    chars
+        .iter()
+        .enumerate()
+        .filter(|(_, &c)| c == target)
+        .nth(count - 1)
+        .map(|(position, _)| position)
 }
@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+
+/// What `jim` was started with.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Args {
+    pub files: Vec<FileArg>,
+    /// Where to start in the first file, from a `+` argument.
+    pub start: Option<Start>,
+    /// `-R`, buffers can't be written.
+    pub readonly: bool,
+    /// `-d`, the files are shown side by side with their differences.
+    pub diff: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct FileArg {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Start {
+    /// `+42`, or `+` alone for the last line.
+    Line(Option<usize>),
+    /// `+/pattern`, the first line containing the pattern.
+    Search(String),
+}
+
+impl Args {
+    /// Parses the arguments after the program name. Options and `+`
+    /// arguments may go anywhere before `--`, everything after it is a
+    /// file.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut only_files = false;
+        for arg in args {
+            if only_files {
+                parsed.files.push(FileArg::new(&arg));
+                continue;
+            }
+            match arg.as_str() {
+                "--" => only_files = true,
+                "-R" => parsed.readonly = true,
+                "-d" => parsed.diff = true,
+                "+" => parsed.start = Some(Start::Line(None)),
+                _ if arg.starts_with("+/") => {
+                    parsed.start = Some(Start::Search(arg[2..].to_string()));
+                }
+                _ if arg.starts_with('+') => match arg[1..].parse() {
+                    Ok(line) => parsed.start = Some(Start::Line(Some(line))),
+                    Err(_) => return Err(format!("Unknown argument: {arg}")),
+                },
+                _ if arg.starts_with('-') && arg.len() > 1 => {
+                    return Err(format!("Unknown option: {arg}"));
+                }
+                _ => parsed.files.push(FileArg::new(&arg)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+impl FileArg {
+    /// Reads `file:line` and `file:line:col` as compilers print them,
+    /// unless a file with that whole name exists.
+    fn new(arg: &str) -> Self {
+        let whole = Self {
+            path: PathBuf::from(arg),
+            line: None,
+            column: None,
+        };
+        if Path::new(arg).exists() {
+            return whole;
+        }
+
+        let number = |text: &str| text.parse::<usize>().ok();
+        let Some((rest, last)) = arg.trim_end_matches(':').rsplit_once(':') else {
+            return whole;
+        };
+        let Some(last) = number(last) else {
+            return whole;
+        };
+        match rest.rsplit_once(':') {
+            Some((path, line)) if number(line).is_some() => Self {
+                path: PathBuf::from(path),
+                line: number(line),
+                column: Some(last),
+            },
+            _ => Self {
+                path: PathBuf::from(rest),
+                line: Some(last),
+                column: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn file(path: &str, line: Option<usize>, column: Option<usize>) -> FileArg {
+        FileArg {
+            path: PathBuf::from(path),
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn plus_arguments_pick_where_to_start() {
+        let args = parse(&["+42", "a"]).unwrap();
+        assert_eq!(args.start, Some(Start::Line(Some(42))));
+        assert_eq!(args.files, vec![file("a", None, None)]);
+        assert_eq!(parse(&["+"]).unwrap().start, Some(Start::Line(None)));
+        assert_eq!(
+            parse(&["a", "+/fn (main|run)"]).unwrap().start,
+            Some(Start::Search("fn (main|run)".to_string()))
+        );
+        assert!(parse(&["+x"]).is_err());
+    }
+
+    #[test]
+    fn options_go_anywhere_before_double_dash() {
+        let args = parse(&["a", "-R", "b", "-d"]).unwrap();
+        assert!(args.readonly);
+        assert!(args.diff);
+        assert_eq!(args.files.len(), 2);
+        assert!(parse(&["-x"]).is_err());
+
+        let args = parse(&["--", "-R", "+3", "-"]).unwrap();
+        assert!(!args.readonly);
+        assert_eq!(args.start, None);
+        assert_eq!(
+            args.files,
+            vec![
+                file("-R", None, None),
+                file("+3", None, None),
+                file("-", None, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn files_take_a_line_and_column_after_colons() {
+        let args = parse(&[
+            "/no/such/dir/a.rs:12",
+            "/no/such/dir/b.rs:3:7:",
+            "/no/such/dir/c.rs:x",
+            "/no/such/dir/d:e:5",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.files,
+            vec![
+                file("/no/such/dir/a.rs", Some(12), None),
+                file("/no/such/dir/b.rs", Some(3), Some(7)),
+                file("/no/such/dir/c.rs:x", None, None),
+                file("/no/such/dir/d:e", Some(5), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn existing_files_keep_their_colons() {
+        let directory = std::env::temp_dir().join(format!("jim-{}-cli", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("a:12");
+        std::fs::write(&path, "").unwrap();
+
+        let args = Args::parse([path.to_string_lossy().into_owned()]).unwrap();
+        assert_eq!(
+            args.files,
+            vec![FileArg {
+                path,
+                line: None,
+                column: None
+            }]
+        );
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
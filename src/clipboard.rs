@@ -0,0 +1,324 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env, fmt,
+    io::{self, Write},
+    path::Path,
+    process::{Command, Stdio},
+    rc::Rc,
+};
+
+use crossterm::{execute, style::Print};
+
+/// Which system selection a register maps to, `+` is the clipboard and `*`
+/// the primary selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SystemSelection {
+    Clipboard,
+    Primary,
+}
+
+impl SystemSelection {
+    pub fn from_register(name: char) -> Option<Self> {
+        match name {
+            '+' => Some(SystemSelection::Clipboard),
+            '*' => Some(SystemSelection::Primary),
+            _ => None,
+        }
+    }
+}
+
+pub trait ClipboardProvider: fmt::Debug {
+    fn copy(&mut self, selection: SystemSelection, text: &str) -> io::Result<()>;
+
+    fn paste(&self, selection: SystemSelection) -> io::Result<String>;
+}
+
+/// Sends copies to the terminal with an OSC 52 escape, which works over
+/// SSH, and also to a copy command like `xclip` when one is set. Pasting
+/// reads the paste command, or what was last copied if there is none, since
+/// few terminals allow reading the clipboard back through OSC 52.
+#[derive(Debug, Default)]
+pub struct SystemClipboard {
+    pub osc52: bool,
+    pub copy_command: Option<Vec<String>>,
+    pub paste_command: Option<Vec<String>>,
+    copied: HashMap<SystemSelection, String>,
+}
+
+impl SystemClipboard {
+    /// Uses `$JIM_COPY_COMMAND` and `$JIM_PASTE_COMMAND` if set, otherwise
+    /// the first of wl-copy, xclip and pbcopy that is installed.
+    pub fn detect() -> Self {
+        let from_env = |name: &str| {
+            let command = env::var(name).ok()?;
+            let words: Vec<String> = command.split_whitespace().map(String::from).collect();
+            (!words.is_empty()).then_some(words)
+        };
+
+        let (copy_command, paste_command) =
+            match (from_env("JIM_COPY_COMMAND"), from_env("JIM_PASTE_COMMAND")) {
+                (None, None) => Self::detect_commands().unzip(),
+                commands => commands,
+            };
+
+        Self {
+            osc52: true,
+            copy_command,
+            paste_command,
+            copied: HashMap::new(),
+        }
+    }
+
+    fn detect_commands() -> Option<(Vec<String>, Vec<String>)> {
+        let words = |command: &str| command.split(' ').map(String::from).collect();
+        let candidates = [
+            ("WAYLAND_DISPLAY", "wl-copy", "wl-paste --no-newline"),
+            (
+                "DISPLAY",
+                "xclip -selection clipboard",
+                "xclip -selection clipboard -o",
+            ),
+            ("", "pbcopy", "pbpaste"),
+        ];
+        candidates
+            .into_iter()
+            .find(|(display, copy, _)| {
+                (display.is_empty() || env::var_os(display).is_some())
+                    && is_in_path(copy.split(' ').next().unwrap())
+            })
+            .map(|(_, copy, paste)| (words(copy), words(paste)))
+    }
+
+    fn write_osc52(selection: SystemSelection, text: &str) -> io::Result<()> {
+        let target = match selection {
+            SystemSelection::Clipboard => 'c',
+            SystemSelection::Primary => 'p',
+        };
+        let sequence = format!("\x1b]52;{target};{}\x07", base64(text.as_bytes()));
+        execute!(io::stdout(), Print(sequence))
+    }
+}
+
+impl ClipboardProvider for SystemClipboard {
+    fn copy(&mut self, selection: SystemSelection, text: &str) -> io::Result<()> {
+        self.copied.insert(selection, text.to_string());
+        if self.osc52 {
+            Self::write_osc52(selection, text)?;
+        }
+        if let Some(command) = &self.copy_command {
+            run_copy_command(command, selection, text)?;
+        }
+        Ok(())
+    }
+
+    fn paste(&self, selection: SystemSelection) -> io::Result<String> {
+        match &self.paste_command {
+            Some(command) => run_paste_command(command, selection),
+            None => Ok(self.copied.get(&selection).cloned().unwrap_or_default()),
+        }
+    }
+}
+
+/// An in-memory clipboard. Clones share their contents, so a test can keep
+/// one to look at what the editor copied.
+#[derive(Debug, Default, Clone)]
+pub struct FakeClipboard {
+    contents: Rc<RefCell<HashMap<SystemSelection, String>>>,
+}
+
+impl FakeClipboard {
+    pub fn get(&self, selection: SystemSelection) -> Option<String> {
+        self.contents.borrow().get(&selection).cloned()
+    }
+
+    pub fn set(&self, selection: SystemSelection, text: &str) {
+        self.contents
+            .borrow_mut()
+            .insert(selection, text.to_string());
+    }
+}
+
+impl ClipboardProvider for FakeClipboard {
+    fn copy(&mut self, selection: SystemSelection, text: &str) -> io::Result<()> {
+        self.set(selection, text);
+        Ok(())
+    }
+
+    fn paste(&self, selection: SystemSelection) -> io::Result<String> {
+        Ok(self.get(selection).unwrap_or_default())
+    }
+}
+
+#[derive(Debug)]
+pub struct Clipboard {
+    provider: Box<dyn ClipboardProvider>,
+}
+
+impl Clipboard {
+    pub fn new(provider: impl ClipboardProvider + 'static) -> Self {
+        Self {
+            provider: Box::new(provider),
+        }
+    }
+
+    pub fn copy(&mut self, selection: SystemSelection, text: &str) -> io::Result<()> {
+        self.provider.copy(selection, text)
+    }
+
+    pub fn paste(&self, selection: SystemSelection) -> io::Result<String> {
+        self.provider.paste(selection)
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new(SystemClipboard::detect())
+    }
+}
+
+fn run_copy_command(command: &[String], selection: SystemSelection, text: &str) -> io::Result<()> {
+    let mut child = selection_command(command, selection)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(text.as_bytes())?;
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "{} exited with {status}",
+            command[0]
+        )));
+    }
+    Ok(())
+}
+
+fn run_paste_command(command: &[String], selection: SystemSelection) -> io::Result<String> {
+    let output = selection_command(command, selection)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{} exited with {}",
+            command[0], output.status
+        )));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Builds `command`, pointing the tools that know about it at the primary
+/// selection for `*`.
+fn selection_command(command: &[String], selection: SystemSelection) -> Command {
+    let mut process = Command::new(&command[0]);
+    for argument in &command[1..] {
+        if selection == SystemSelection::Primary && argument == "clipboard" {
+            process.arg("primary");
+        } else {
+            process.arg(argument);
+        }
+    }
+    if selection == SystemSelection::Primary && command[0].starts_with("wl-") {
+        process.arg("--primary");
+    }
+    process
+}
+
+fn is_in_path(program: &str) -> bool {
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths).any(|directory| Path::new(&directory).join(program).is_file())
+    })
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        register::{Register, Registers},
+        selection::SelectionKind,
+    };
+
+    fn registers_with(clipboard: &FakeClipboard) -> Registers {
+        let mut registers = Registers::default();
+        registers.clipboard = Clipboard::new(clipboard.clone());
+        registers
+    }
+
+    #[test]
+    fn plus_and_star_registers_go_through_the_clipboard() {
+        let clipboard = FakeClipboard::default();
+        let mut registers = registers_with(&clipboard);
+        let word = Register::new("word".to_string(), SelectionKind::Characterwise);
+        let line = Register::new("line\n".to_string(), SelectionKind::Linewise);
+
+        registers.yank(Some('+'), word.clone()).unwrap();
+        registers.delete(Some('*'), line.clone()).unwrap();
+
+        assert_eq!(
+            clipboard.get(SystemSelection::Clipboard).as_deref(),
+            Some("word")
+        );
+        assert_eq!(
+            clipboard.get(SystemSelection::Primary).as_deref(),
+            Some("line\n")
+        );
+        assert_eq!(registers.get('+'), Some(word));
+        assert_eq!(registers.get('*'), Some(line.clone()));
+        assert_eq!(registers.get('"'), Some(line));
+    }
+
+    #[test]
+    fn text_copied_elsewhere_can_be_pasted() {
+        let clipboard = FakeClipboard::default();
+        let registers = registers_with(&clipboard);
+        assert_eq!(registers.get('+'), None);
+
+        clipboard.set(SystemSelection::Clipboard, "from outside");
+        assert_eq!(
+            registers.get('+'),
+            Some(Register::new(
+                "from outside".to_string(),
+                SelectionKind::Characterwise
+            ))
+        );
+        assert_eq!(registers.get('*'), None);
+    }
+
+    #[test]
+    fn base64_pads_partial_groups() {
+        // The test vectors from RFC 4648.
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xff, 0xfe]), "//4=");
+    }
+}
@@ -0,0 +1,245 @@
+use std::{collections::VecDeque, fs, path::Path};
+
+/// How many lines a `LineHistory` keeps, vim's default 'history'.
+const HISTORY: usize = 50;
+
+/// The text typed at the `:`, `/` or `?` prompt and where in it the cursor
+/// is, in bytes.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CommandLine {
+    text: String,
+    cursor: usize,
+}
+
+impl CommandLine {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// The text up to the cursor, what completion and history look at.
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.cursor]
+    }
+
+    /// Where the cursor is in chars, for drawing it.
+    pub fn cursor_column(&self) -> usize {
+        self.before_cursor().chars().count()
+    }
+
+    /// Replaces the text, leaving the cursor at its end.
+    pub fn set(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = self.text.len();
+    }
+
+    /// Empties the line, returning what was on it.
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text)
+    }
+
+    pub fn clear(&mut self) {
+        self.take();
+    }
+
+    pub fn insert(&mut self, char: char) {
+        self.text.insert(self.cursor, char);
+        self.cursor += char.len_utf8();
+    }
+
+    /// Replaces the text from `start` to the cursor with `text`.
+    pub fn replace_before_cursor(&mut self, start: usize, text: &str) {
+        self.text.replace_range(start..self.cursor, text);
+        self.cursor = start + text.len();
+    }
+
+    pub fn backspace(&mut self) {
+        if let Some(char) = self.before_cursor().chars().next_back() {
+            self.cursor -= char.len_utf8();
+            self.text.remove(self.cursor);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+        }
+    }
+
+    pub fn left(&mut self) {
+        if let Some(char) = self.before_cursor().chars().next_back() {
+            self.cursor -= char.len_utf8();
+        }
+    }
+
+    pub fn right(&mut self) {
+        if let Some(char) = self.text[self.cursor..].chars().next() {
+            self.cursor += char.len_utf8();
+        }
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// Moves to the start of the word before the cursor, like `<S-Left>`.
+    pub fn word_left(&mut self) {
+        self.cursor = self.word_start();
+    }
+
+    /// Moves past the end of the word after the cursor, like `<S-Right>`.
+    pub fn word_right(&mut self) {
+        let rest = &self.text[self.cursor..];
+        let word = rest.trim_start();
+        let skipped = rest.len() - word.len();
+        let end = word.find(char::is_whitespace).unwrap_or(word.len());
+        self.cursor += skipped + end;
+    }
+
+    /// Deletes the word before the cursor, like `<C-w>`.
+    pub fn delete_word(&mut self) {
+        let start = self.word_start();
+        self.replace_before_cursor(start, "");
+    }
+
+    /// Deletes everything before the cursor, like `<C-u>`.
+    pub fn delete_to_start(&mut self) {
+        self.replace_before_cursor(0, "");
+    }
+
+    /// The start of the word before the cursor and the spaces after it. A
+    /// word is a run of letters, digits and `_`, or of other characters.
+    fn word_start(&self) -> usize {
+        let before = self.before_cursor();
+        let trimmed = before.trim_end();
+        let is_word = |char: char| char.is_alphanumeric() || char == '_';
+        let Some(last) = trimmed.chars().next_back() else {
+            return 0;
+        };
+        let same_kind = |char: char| !char.is_whitespace() && is_word(char) == is_word(last);
+        trimmed
+            .char_indices()
+            .rev()
+            .take_while(|&(_, char)| same_kind(char))
+            .last()
+            .map_or(trimmed.len(), |(index, _)| index)
+    }
+}
+
+/// Lines typed at a prompt before, the oldest first, for going back to
+/// them with `<Up>` and `<Down>`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LineHistory {
+    lines: VecDeque<String>,
+}
+
+impl LineHistory {
+    /// Adds `line` as the newest, dropping an earlier copy and the oldest
+    /// lines past `HISTORY`.
+    pub fn remember(&mut self, line: &str) {
+        if line.is_empty() {
+            return;
+        }
+        self.lines.retain(|old| old != line);
+        self.lines.push_back(line.to_string());
+        while self.lines.len() > HISTORY {
+            self.lines.pop_front();
+        }
+    }
+
+    /// The next line older than `from` that starts with `prefix`, counting
+    /// from the newest, and its index. `from` is `None` when not browsing
+    /// yet.
+    pub fn older(&self, prefix: &str, from: Option<usize>) -> Option<(usize, &str)> {
+        let start = from.map_or(0, |from| from + 1);
+        (start..self.lines.len()).find_map(|index| self.matching(index, prefix))
+    }
+
+    /// The next line newer than `from` that starts with `prefix`. `None`
+    /// past the newest, where the prefix itself goes back on the line.
+    pub fn newer(&self, prefix: &str, from: usize) -> Option<(usize, &str)> {
+        (0..from)
+            .rev()
+            .find_map(|index| self.matching(index, prefix))
+    }
+
+    fn matching(&self, index: usize, prefix: &str) -> Option<(usize, &str)> {
+        let line = self.lines.get(self.lines.len() - 1 - index)?;
+        line.starts_with(prefix).then_some((index, line.as_str()))
+    }
+}
+
+/// Browsing a `LineHistory` from a prompt. Only lines starting with what
+/// was typed before the first `<Up>` come up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Browse {
+    pub prefix: String,
+    /// The line shown, counted from the newest. `None` for the prefix.
+    pub index: Option<usize>,
+}
+
+/// Cycling through the ways to complete the word before the cursor with
+/// `<Tab>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    /// Where the completed word starts, in bytes.
+    pub start: usize,
+    pub candidates: Vec<String>,
+    pub index: usize,
+}
+
+/// What the argument of a command completes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentKind {
+    File,
+    Buffer,
+    None,
+}
+
+impl ArgumentKind {
+    /// The kind of argument the command with the full name `command`
+    /// takes.
+    pub fn of(command: &str) -> Self {
+        match command {
+            "edit" | "write" | "wq" | "xit" | "exit" => ArgumentKind::File,
+            "buffer" | "bdelete" => ArgumentKind::Buffer,
+            _ => ArgumentKind::None,
+        }
+    }
+}
+
+/// The paths starting with `prefix`, directories ending in `/`. Hidden
+/// files only come up when `prefix` names them with a `.`.
+pub fn complete_path(prefix: &str) -> Vec<String> {
+    let (directory, name) = match prefix.rfind('/') {
+        Some(index) => prefix.split_at(index + 1),
+        None => ("", prefix),
+    };
+    let read_from = if directory.is_empty() { "." } else { directory };
+    let Ok(entries) = fs::read_dir(Path::new(read_from)) else {
+        return vec![];
+    };
+    let mut paths: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if !file_name.starts_with(name)
+                || (file_name.starts_with('.') && !name.starts_with('.'))
+            {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{directory}{file_name}{slash}"))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
@@ -2,7 +2,7 @@ use nalgebra::Vector2;
 
 use crate::file::JimFile;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy)]
 pub struct Cursor {
     pub xy_pos: Vector2<usize>,
     pub index: usize,
@@ -20,16 +20,15 @@ impl Cursor {
     }
 
     pub fn write_char_to(&mut self, char: char, file: &mut JimFile) {
-        file.contents.insert(self.index, char);
+        file.insert_char(self.index, char);
 
         if char == '\n' {
-            self.move_down(&file);
-            self.xy_pos = self.get_position(&file);
-            self.move_full_left();
+            self.xy_pos = Vector2::new(0, self.xy_pos.y + 1);
         } else {
+            self.xy_pos = self.get_position(file);
             self.xy_pos.x += 1;
-            self.index += 1;
         }
+        self.index += 1;
     }
 
     pub fn backspace(&mut self, file: &mut JimFile) {
@@ -37,47 +36,45 @@ impl Cursor {
             return;
         }
 
-        if file.contents.chars().nth(self.index - 1).unwrap() == '\n' {
-            self.index -= 1;
+        if file.contents.char(self.index - 1) == Some('\n') {
             self.xy_pos.y -= 1;
-            self.xy_pos.x = self.current_line_length(file).unwrap();
-            self.xy_pos = self.get_position(file);
+            self.xy_pos.x = self.current_line_length(file).unwrap_or_default();
         } else {
-            self.move_left(file);
+            self.xy_pos = self.get_position(file);
+            self.xy_pos.x = self.xy_pos.x.saturating_sub(1);
         }
-        file.contents.remove(self.index);
+        self.index -= 1;
+        file.remove(self.index..self.index + 1);
+        self.update_index(file);
     }
 
-    pub fn move_full_left(&mut self) {
-        self.index -= self.xy_pos.x;
+    pub fn move_full_left(&mut self, file: &JimFile) {
         self.xy_pos.x = 0;
+        self.update_index(file);
     }
 
     pub fn move_full_right(&mut self, file: &JimFile) {
         if let Some(length) = self.current_line_length(file) {
-            let distance = length - self.xy_pos.x;
-            self.index += distance;
-            self.xy_pos.x += distance;
+            self.xy_pos.x = length;
+            self.update_index(file);
         }
     }
 
     pub fn move_right(&mut self, file: &JimFile) {
         if let Some(length) = self.current_line_length(file) {
-            if self.xy_pos.x + 1 < length {
-                self.index += 1;
-                self.xy_pos.x += 1;
+            let position = self.get_position(file);
+            if position.x + 1 < length {
+                self.xy_pos.x = position.x + 1;
+                self.update_index(file);
             }
         }
     }
 
     pub fn move_left(&mut self, file: &JimFile) {
-        if self.xy_pos.x > 0 {
-            let visable_pos = self.get_position(file);
-            if visable_pos.x > 0 {
-                self.xy_pos = visable_pos;
-                self.index -= 1;
-                self.xy_pos.x -= 1;
-            }
+        let position = self.get_position(file);
+        if position.x > 0 {
+            self.xy_pos.x = position.x - 1;
+            self.update_index(file);
         }
     }
 
@@ -86,39 +83,25 @@ impl Cursor {
             return;
         }
 
-        self.index -= self.get_position(file).x + 1;
         self.xy_pos.y -= 1;
-
-        if let Some(length) = self.current_line_length(file) {
-            self.index -= length - self.xy_pos.x.clamp(0, length);
-        } else {
-            eprintln!("Could not move UP")
-        }
+        self.update_index(file);
     }
 
     pub fn move_down(&mut self, file: &JimFile) {
-        let lines: Vec<&str> = file.contents.lines().collect();
-        if lines.len() - 1 <= self.xy_pos.y {
+        if file.contents.len_lines() <= self.xy_pos.y + 1 {
             return;
         }
 
-        if let Some(length) = self.current_line_length(file) {
-            self.index += length - self.get_position(file).x + 1;
-
-            self.xy_pos.y += 1;
-            self.index += self.get_position(file).x;
-        } else {
-            eprintln!("Could not move DOWN")
-        }
+        self.xy_pos.y += 1;
+        self.update_index(file);
     }
 
     pub fn force_move_right(&mut self, file: &JimFile) {
-        if file.contents.chars().nth(self.index - 1).unwrap() == '\n' {
-            return;
+        let position = self.get_position(file);
+        if position.x < self.current_line_length(file).unwrap_or_default() {
+            self.xy_pos.x = position.x + 1;
+            self.update_index(file);
         }
-
-        self.xy_pos.x += 1;
-        self.index += 1;
     }
 
     pub fn get_position(&self, file: &JimFile) -> Vector2<usize> {
@@ -126,12 +109,23 @@ impl Cursor {
         Vector2::new(self.xy_pos.x.clamp(0, length), self.xy_pos.y)
     }
 
-    fn current_line_length(&self, file: &JimFile) -> Option<usize> {
-        Self::line_length(&file.contents, self.xy_pos.y)
+    pub fn set_index(&mut self, index: usize, file: &JimFile) {
+        let index = index.min(file.contents.len_chars());
+        let line = file.contents.char_to_line(index);
+        let line_start = file.contents.line_to_char(line);
+        // Past the line break that ends the text is the end of the last line.
+        let column = (index - line_start).min(file.contents.line_len(line).unwrap_or_default());
+        self.xy_pos = Vector2::new(column, line);
+        self.index = line_start + column;
     }
 
-    fn line_length(text: &str, pos: usize) -> Option<usize> {
-        let lines: Vec<&str> = text.lines().collect();
-        lines.get(pos).map(|line| line.chars().count())
+    /// Recomputes `index` from `xy_pos`, clamping the column to the line.
+    pub fn update_index(&mut self, file: &JimFile) {
+        let position = self.get_position(file);
+        self.index = file.contents.line_to_char(position.y) + position.x;
+    }
+
+    fn current_line_length(&self, file: &JimFile) -> Option<usize> {
+        file.contents.line_len(self.xy_pos.y)
     }
 }
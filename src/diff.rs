@@ -0,0 +1,108 @@
+/// Above this many line pairs to compare, the lines between the common
+/// start and end are all taken as changed instead.
+const MAX_COMPARISONS: usize = 4_000_000;
+
+/// Marks the lines of `old` and `new` that aren't part of their longest
+/// common subsequence, the ones `-d` highlights.
+pub fn changed_lines(old: &[String], new: &[String]) -> (Vec<bool>, Vec<bool>) {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut old_changed = vec![false; old.len()];
+    let mut new_changed = vec![false; new.len()];
+    if old_middle.len() * new_middle.len() > MAX_COMPARISONS {
+        old_changed[prefix..old.len() - suffix].fill(true);
+        new_changed[prefix..new.len() - suffix].fill(true);
+        return (old_changed, new_changed);
+    }
+
+    // lengths[i][j] is the length of the longest common subsequence of
+    // old_middle[i..] and new_middle[j..].
+    let width = new_middle.len() + 1;
+    let mut lengths = vec![0u32; (old_middle.len() + 1) * width];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            lengths[i * width + j] = if old_middle[i] == new_middle[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() || j < new_middle.len() {
+        if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+            i += 1;
+            j += 1;
+        } else if j == new_middle.len()
+            || (i < old_middle.len() && lengths[(i + 1) * width + j] >= lengths[i * width + j + 1])
+        {
+            old_changed[prefix + i] = true;
+            i += 1;
+        } else {
+            new_changed[prefix + j] = true;
+            j += 1;
+        }
+    }
+    (old_changed, new_changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.chars().map(String::from).collect()
+    }
+
+    fn changed(old: &str, new: &str) -> (String, String) {
+        let show = |changed: Vec<bool>| {
+            changed
+                .into_iter()
+                .map(|changed| if changed { '+' } else { '.' })
+                .collect()
+        };
+        let (old, new) = changed_lines(&lines(old), &lines(new));
+        (show(old), show(new))
+    }
+
+    #[test]
+    fn equal_lines_are_unchanged() {
+        assert_eq!(changed("abc", "abc"), ("...".into(), "...".into()));
+        assert_eq!(changed("", ""), ("".into(), "".into()));
+    }
+
+    #[test]
+    fn added_and_removed_lines_are_changed() {
+        assert_eq!(changed("abc", "abxc"), ("...".into(), "..+.".into()));
+        assert_eq!(changed("abxc", "abc"), ("..+.".into(), "...".into()));
+        assert_eq!(changed("", "ab"), ("".into(), "++".into()));
+        assert_eq!(changed("ab", ""), ("++".into(), "".into()));
+    }
+
+    #[test]
+    fn only_lines_outside_the_common_subsequence_are_changed() {
+        assert_eq!(changed("axbyc", "abzc"), (".+.+.".into(), "..+.".into()));
+        let (old, new) = changed("abcd", "dcba");
+        assert_eq!(old.matches('+').count(), 3);
+        assert_eq!(new.matches('+').count(), 3);
+    }
+
+    #[test]
+    fn repeated_lines_at_both_ends_are_not_counted_twice() {
+        assert_eq!(changed("aa", "aaa"), ("..".into(), "..+".into()));
+        assert_eq!(changed("aba", "a"), (".++".into(), ".".into()));
+    }
+}
@@ -0,0 +1,310 @@
+/// A `:` command line split into its parts: `:3,7d x` has the range `3,7`,
+/// the name `d` and the arguments `x`, `:w! foo` has the bang set.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExCommand {
+    pub range: Option<LineRange>,
+    pub name: String,
+    pub bang: bool,
+    pub arguments: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineRange {
+    /// `%`, every line.
+    Whole,
+    /// One address, or two separated by `,`.
+    Lines(Address, Option<Address>),
+}
+
+/// A line number, `.` or `$`, moved by any `+N` and `-N` after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Address {
+    base: Base,
+    offset: isize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Base {
+    Current,
+    Last,
+    Line(usize),
+}
+
+impl ExCommand {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim_start_matches(|char: char| char == ':' || char.is_whitespace());
+        let (range, rest) = parse_range(text)?;
+        let rest = rest.trim_start();
+
+        // Names are letters, a few commands like `:&` are a single symbol.
+        let name_length = match rest.chars().next() {
+            Some(char) if char.is_ascii_alphabetic() => rest
+                .find(|char: char| !char.is_ascii_alphabetic())
+                .unwrap_or(rest.len()),
+            Some(char) if "&<>=".contains(char) => 1,
+            _ => 0,
+        };
+        let (name, rest) = rest.split_at(name_length);
+        let (bang, rest) = match rest.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        if name.is_empty() && !rest.trim().is_empty() {
+            return Err(format!("Not an editor command: {}", text.trim()));
+        }
+        Ok(Self {
+            range,
+            name: name.to_string(),
+            bang,
+            arguments: rest.trim().to_string(),
+        })
+    }
+}
+
+/// Reads the `/pattern/` that `:s` and `:g` start with, returning the
+/// delimiter, the pattern and what's after it if it was closed. Any
+/// character that isn't a letter, digit, space or `\` can take the place
+/// of `/`.
+pub fn parse_pattern(arguments: &str) -> Result<(char, String, Option<&str>), String> {
+    let Some(delimiter) = arguments.chars().next() else {
+        return Err("Missing pattern".to_string());
+    };
+    if delimiter.is_alphanumeric() || delimiter.is_whitespace() || delimiter == '\\' {
+        return Err("Regular expression can't be delimited by letters".to_string());
+    }
+    let (pattern, rest) = split_at_delimiter(&arguments[delimiter.len_utf8()..], delimiter);
+    Ok((delimiter, pattern, rest))
+}
+
+/// The text up to the first `delimiter` that isn't escaped, and what's
+/// after it if there was one. Escaped delimiters lose their backslash.
+pub fn split_at_delimiter(text: &str, delimiter: char) -> (String, Option<&str>) {
+    let mut part = String::new();
+    let mut chars = text.char_indices();
+    while let Some((index, char)) = chars.next() {
+        if char == delimiter {
+            return (part, Some(&text[index + char.len_utf8()..]));
+        }
+        if char == '\\' {
+            match chars.next() {
+                Some((_, next)) if next == delimiter => part.push(next),
+                Some((_, next)) => {
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => part.push('\\'),
+            }
+            continue;
+        }
+        part.push(char);
+    }
+    (part, None)
+}
+
+impl LineRange {
+    /// The first and last line, counted from 0. The lines of a backwards
+    /// range are swapped.
+    pub fn resolve(&self, current: usize, line_count: usize) -> Result<(usize, usize), String> {
+        let (first, last) = match self {
+            LineRange::Whole => return Ok((0, line_count.saturating_sub(1))),
+            LineRange::Lines(first, last) => (first, last.unwrap_or(*first)),
+        };
+        let first = first.resolve(current, line_count)?;
+        let last = last.resolve(current, line_count)?;
+        Ok((first.min(last), first.max(last)))
+    }
+
+    /// The last line, where commands that take one line like `:10` go.
+    pub fn last_line(&self, current: usize, line_count: usize) -> Result<usize, String> {
+        match self {
+            LineRange::Lines(first, None) => first.resolve(current, line_count),
+            LineRange::Lines(_, Some(last)) => last.resolve(current, line_count),
+            LineRange::Whole => Ok(line_count.saturating_sub(1)),
+        }
+    }
+}
+
+impl Address {
+    /// The line counted from 0. Line 0 is taken as the first line.
+    fn resolve(&self, current: usize, line_count: usize) -> Result<usize, String> {
+        let base = match self.base {
+            Base::Current => current + 1,
+            Base::Last => line_count,
+            Base::Line(line) => line,
+        };
+        let line = isize::try_from(base)
+            .ok()
+            .and_then(|base| base.checked_add(self.offset))
+            .and_then(|line| usize::try_from(line).ok())
+            .filter(|&line| line <= line_count)
+            .ok_or_else(|| "Invalid range".to_string())?;
+        Ok(line.max(1) - 1)
+    }
+}
+
+fn parse_range(text: &str) -> Result<(Option<LineRange>, &str), String> {
+    if let Some(rest) = text.strip_prefix('%') {
+        return Ok((Some(LineRange::Whole), rest));
+    }
+    let (first, rest) = parse_address(text)?;
+    let Some(rest) = rest.strip_prefix(',') else {
+        return Ok((first.map(|first| LineRange::Lines(first, None)), rest));
+    };
+    let (last, rest) = parse_address(rest)?;
+    let current = Address {
+        base: Base::Current,
+        offset: 0,
+    };
+    let range = LineRange::Lines(first.unwrap_or(current), Some(last.unwrap_or(current)));
+    Ok((Some(range), rest))
+}
+
+fn parse_address(text: &str) -> Result<(Option<Address>, &str), String> {
+    let (base, mut rest) = match text.chars().next() {
+        Some('.') => (Some(Base::Current), &text[1..]),
+        Some('$') => (Some(Base::Last), &text[1..]),
+        Some(char) if char.is_ascii_digit() => {
+            let (line, rest) = parse_number(text)?;
+            (line.map(Base::Line), rest)
+        }
+        _ => (None, text),
+    };
+
+    let mut offset: Option<isize> = None;
+    loop {
+        let sign = match rest.chars().next() {
+            Some('+') => 1,
+            Some('-') => -1,
+            _ => break,
+        };
+        let (amount, after) = parse_number(&rest[1..])?;
+        let amount = isize::try_from(amount.unwrap_or(1)).map_err(|_| "Invalid range")?;
+        offset = Some(offset.unwrap_or(0).saturating_add(sign * amount));
+        rest = after;
+    }
+
+    if base.is_none() && offset.is_none() {
+        return Ok((None, text));
+    }
+    let address = Address {
+        base: base.unwrap_or(Base::Current),
+        offset: offset.unwrap_or(0),
+    };
+    Ok((Some(address), rest))
+}
+
+fn parse_number(text: &str) -> Result<(Option<usize>, &str), String> {
+    let length = text
+        .find(|char: char| !char.is_ascii_digit())
+        .unwrap_or(text.len());
+    if length == 0 {
+        return Ok((None, text));
+    }
+    let number = text[..length]
+        .parse()
+        .map_err(|_| "Invalid range".to_string())?;
+    Ok((Some(number), &text[length..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(command: &str, current: usize, line_count: usize) -> Result<(usize, usize), String> {
+        ExCommand::parse(command)?
+            .range
+            .ok_or("No range")?
+            .resolve(current, line_count)
+    }
+
+    #[test]
+    fn parse_splits_range_name_bang_and_arguments() {
+        let command = ExCommand::parse(":3,7d x").unwrap();
+        assert_eq!(command.name, "d");
+        assert_eq!(command.arguments, "x");
+        assert!(!command.bang);
+        assert!(command.range.is_some());
+
+        let command = ExCommand::parse("w! foo bar ").unwrap();
+        assert_eq!(command.name, "w");
+        assert!(command.bang);
+        assert_eq!(command.arguments, "foo bar");
+        assert_eq!(command.range, None);
+
+        let command = ExCommand::parse("s/a/b/").unwrap();
+        assert_eq!(command.name, "s");
+        assert_eq!(command.arguments, "/a/b/");
+    }
+
+    #[test]
+    fn parse_takes_one_symbol_names() {
+        let command = ExCommand::parse("%&&").unwrap();
+        assert_eq!(command.range, Some(LineRange::Whole));
+        assert_eq!(command.name, "&");
+        assert_eq!(command.arguments, "&");
+    }
+
+    #[test]
+    fn parse_rejects_text_without_a_name() {
+        assert!(ExCommand::parse("3,4 !?").is_err());
+        assert_eq!(ExCommand::parse("").unwrap(), ExCommand::default());
+    }
+
+    #[test]
+    fn ranges_resolve_to_lines_from_zero() {
+        assert_eq!(lines("%d", 4, 10), Ok((0, 9)));
+        assert_eq!(lines("3,7d", 0, 10), Ok((2, 6)));
+        assert_eq!(lines(".,$d", 4, 10), Ok((4, 9)));
+        assert_eq!(lines(".-1,.+2d", 4, 10), Ok((3, 6)));
+        assert_eq!(lines("+,++d", 4, 10), Ok((5, 6)));
+        assert_eq!(lines("$-3d", 0, 10), Ok((6, 6)));
+        assert_eq!(lines(",5d", 1, 10), Ok((1, 4)));
+        assert_eq!(lines("0d", 4, 10), Ok((0, 0)));
+    }
+
+    #[test]
+    fn backwards_ranges_are_swapped() {
+        assert_eq!(lines("7,3d", 0, 10), Ok((2, 6)));
+    }
+
+    #[test]
+    fn ranges_past_the_buffer_are_invalid() {
+        assert!(lines("11d", 0, 10).is_err());
+        assert!(lines("$+1d", 0, 10).is_err());
+        assert!(lines(".-5d", 2, 10).is_err());
+        assert!(ExCommand::parse("99999999999999999999999d").is_err());
+    }
+
+    #[test]
+    fn last_line_is_the_second_address() {
+        let range = ExCommand::parse("3,7").unwrap().range.unwrap();
+        assert_eq!(range.last_line(0, 10), Ok(6));
+        let range = ExCommand::parse("5").unwrap().range.unwrap();
+        assert_eq!(range.last_line(0, 10), Ok(4));
+    }
+
+    #[test]
+    fn parse_pattern_takes_any_delimiter() {
+        assert_eq!(
+            parse_pattern("/a/b/g"),
+            Ok(('/', "a".to_string(), Some("b/g")))
+        );
+        assert_eq!(
+            parse_pattern("#a/b#c"),
+            Ok(('#', "a/b".to_string(), Some("c")))
+        );
+        assert_eq!(parse_pattern("/abc"), Ok(('/', "abc".to_string(), None)));
+        assert!(parse_pattern("").is_err());
+        assert!(parse_pattern("xaxbx").is_err());
+        assert!(parse_pattern("\\a\\b\\").is_err());
+    }
+
+    #[test]
+    fn split_at_delimiter_keeps_other_escapes() {
+        assert_eq!(
+            split_at_delimiter(r"a\/b\.c/rest", '/'),
+            (r"a/b\.c".to_string(), Some("rest"))
+        );
+        assert_eq!(split_at_delimiter(r"ab\", '/'), (r"ab\".to_string(), None));
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::buffer::Buffer;
+
+const PARENT: &str = "../";
+
+/// A directory listing that can be edited like text. Every entry's line
+/// starts with `/<id>` so renamed lines can be told apart from new ones,
+/// names can't have a `/` in front of them so the ids can't clash.
+#[derive(Debug, Default)]
+pub struct Explorer {
+    entries: Vec<String>,
+    /// Changes that are waiting to be confirmed.
+    pub pending: Vec<Change>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Create(PathBuf),
+    Rename(PathBuf, PathBuf),
+    Delete(PathBuf),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Create(path) => write!(f, "CREATE {}", path.display()),
+            Change::Rename(from, to) => write!(f, "RENAME {} -> {}", from.display(), to.display()),
+            Change::Delete(path) => write!(f, "DELETE {}", path.display()),
+        }
+    }
+}
+
+impl Explorer {
+    /// Lists `directory`, directories first with a `/` after their name.
+    pub fn read(directory: &Path) -> io::Result<(Self, Buffer)> {
+        let mut entries = vec![];
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            let mut name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type()?.is_dir() {
+                name.push('/');
+            }
+            entries.push(name);
+        }
+        entries.sort_by_key(|name| (!name.ends_with('/'), name.to_lowercase()));
+
+        let mut listing = vec![];
+        if directory.parent().is_some() {
+            listing.push(PARENT.to_string());
+        }
+        for (id, name) in entries.iter().enumerate() {
+            listing.push(format!("/{id:03} {name}"));
+        }
+        let explorer = Self {
+            entries,
+            pending: vec![],
+        };
+        Ok((explorer, Buffer::from(listing.join("\n").as_str())))
+    }
+
+    /// The name on a line of the listing.
+    pub fn name_on(line: &str) -> &str {
+        match parse_line(line) {
+            Some((_, name)) => name,
+            None => line.trim(),
+        }
+    }
+
+    /// Compares the edited listing in `contents` with what was read. Lines
+    /// without an id are created, entries whose line is gone are deleted and
+    /// entries with a different name are renamed.
+    pub fn changes(&self, directory: &Path, contents: &Buffer) -> Result<Vec<Change>, String> {
+        let listing = contents.to_string();
+        let mut kept: HashMap<usize, &str> = HashMap::new();
+        let mut created = vec![];
+        for line in listing.lines() {
+            let line = line.trim_end();
+            if line.trim().is_empty() || line == PARENT {
+                continue;
+            }
+            match parse_line(line) {
+                Some((id, name)) => {
+                    if id >= self.entries.len() {
+                        return Err(format!("There is no entry /{id:03}"));
+                    }
+                    if kept.insert(id, name).is_some() {
+                        return Err(format!("/{id:03} is listed more than once"));
+                    }
+                }
+                None if line.starts_with('/') => return Err(format!("Can't read \"{line}\"")),
+                None => created.push(line.trim()),
+            }
+        }
+
+        let mut changes = vec![];
+        let mut freed = vec![];
+        for (id, entry) in self.entries.iter().enumerate() {
+            match kept.get(&id) {
+                None => {
+                    changes.push(Change::Delete(directory.join(entry)));
+                    freed.push(directory.join(entry));
+                }
+                Some(&name) if name != entry => {
+                    changes.push(Change::Rename(directory.join(entry), directory.join(name)));
+                    freed.push(directory.join(entry));
+                }
+                Some(_) => {}
+            }
+        }
+        changes.extend(
+            created
+                .into_iter()
+                .map(|name| Change::Create(directory.join(name))),
+        );
+
+        let mut targets = HashSet::new();
+        for change in &changes {
+            let target = match change {
+                Change::Create(path) | Change::Rename(_, path) => path,
+                Change::Delete(_) => continue,
+            };
+            let name = target.strip_prefix(directory).unwrap_or(target);
+            if name
+                .components()
+                .any(|component| !matches!(component, std::path::Component::Normal(_)))
+            {
+                return Err(format!("\"{}\" is not a valid name", name.display()));
+            }
+            if target.exists() && !freed.contains(target) {
+                return Err(format!("\"{}\" already exists", name.display()));
+            }
+            if !targets.insert(target) {
+                return Err(format!("\"{}\" is listed more than once", name.display()));
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Applies the pending changes. Renames go through temporary names
+    /// first so swapping two names doesn't lose either file.
+    pub fn apply(&mut self) -> io::Result<usize> {
+        let changes = std::mem::take(&mut self.pending);
+        let mut renames = vec![];
+        for (index, change) in changes.iter().enumerate() {
+            match change {
+                Change::Delete(path) if path.is_dir() => fs::remove_dir_all(path)?,
+                Change::Delete(path) => fs::remove_file(path)?,
+                Change::Rename(from, to) => {
+                    let name = from.file_name().unwrap_or_default().to_string_lossy();
+                    let temporary = from.with_file_name(format!(".jim-rename-{index}-{name}"));
+                    fs::rename(from, &temporary)?;
+                    renames.push((temporary, to));
+                }
+                Change::Create(_) => {}
+            }
+        }
+        for (temporary, to) in renames {
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(temporary, to)?;
+        }
+        for change in &changes {
+            if let Change::Create(path) = change {
+                if path.to_string_lossy().ends_with('/') {
+                    fs::create_dir_all(path)?;
+                } else {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    // Never truncates, even if the checks above missed
+                    // something.
+                    fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(path)?;
+                }
+            }
+        }
+        Ok(changes.len())
+    }
+}
+
+/// Splits a `/<id> <name>` line.
+fn parse_line(line: &str) -> Option<(usize, &str)> {
+    let (id, name) = line.strip_prefix('/')?.split_once(' ')?;
+    Some((id.parse().ok()?, name.trim()))
+}
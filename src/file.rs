@@ -1,23 +1,587 @@
-use std::path::{Path, PathBuf};
-use std::{fs, io};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::buffer::Buffer;
+use crate::cursor::Cursor;
+use crate::explorer::Explorer;
+use crate::history::{undo_file_path, Edit, History};
+use crate::options::Options;
 
 #[derive(Debug, Default)]
 pub struct JimFile {
     pub path: PathBuf,
-    pub contents: String,
+    pub contents: Buffer,
+    pub history: History,
+    /// Where the cursor was when the buffer was last left.
+    pub cursor: Cursor,
+    /// Set when the buffer lists a directory instead of holding a file.
+    pub explorer: Option<Explorer>,
+    pub readonly: bool,
+    /// The file didn't exist when the buffer was opened and hasn't been
+    /// written yet.
+    pub is_new: bool,
+    /// Edited since the file was last read or written.
+    pub modified: bool,
+    /// The history node the file was last read or written at, undoing back
+    /// to it makes the buffer unmodified again.
+    saved_node: usize,
+    /// Holds text made up by jim, like `:messages`, instead of a file.
+    pub scratch: bool,
+    /// The starts of the lines a `:g` has yet to run on.
+    pub marked_lines: Vec<usize>,
+    /// The file as it was when it was last read or written.
+    disk: Option<DiskState>,
+}
+
+/// Enough of a file to tell whether something else changed it. The hash is
+/// only compared when the cheap checks differ, so touching a file without
+/// changing it doesn't count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DiskState {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl DiskState {
+    fn new(path: &Path, hash: u64) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            hash,
+        })
+    }
 }
 
 impl JimFile {
     pub fn new(path: &Path) -> io::Result<Self> {
-        let contents = fs::read_to_string(path)?;
+        if path.is_dir() {
+            let (explorer, contents) = Explorer::read(path)?;
+            return Ok(Self {
+                path: path.to_path_buf(),
+                contents,
+                explorer: Some(explorer),
+                ..Default::default()
+            });
+        }
+
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self {
+                    path: path.to_path_buf(),
+                    is_new: true,
+                    ..Default::default()
+                });
+            }
+            Err(error) => return Err(error),
+        };
+        let contents = Buffer::from_reader(BufReader::new(file))?;
+        let history = Self::load_history(path, &contents).unwrap_or_default();
+        let saved_node = history.current();
+        let disk = DiskState::new(path, contents.content_hash()).ok();
         Ok(Self {
             path: path.to_path_buf(),
             contents,
+            history,
+            cursor: Cursor::default(),
+            explorer: None,
+            readonly: false,
+            is_new: false,
+            modified: false,
+            saved_node,
+            scratch: false,
+            marked_lines: Vec::new(),
+            disk,
         })
     }
 
-    pub fn save(&self) -> io::Result<()> {
-        fs::write(&self.path, &self.contents)?;
+    /// A buffer showing `text` that isn't backed by a file. `name` is only
+    /// shown, nothing is written to it.
+    pub fn scratch(name: &str, text: &str) -> Self {
+        Self {
+            path: PathBuf::from(name),
+            contents: Buffer::from(text),
+            scratch: true,
+            ..Default::default()
+        }
+    }
+
+    /// Lists the directory of an explorer buffer again, starting a new
+    /// undo history since the old one was about the old listing.
+    pub fn reload_explorer(&mut self) -> io::Result<()> {
+        let (explorer, contents) = Explorer::read(&self.path)?;
+        self.explorer = Some(explorer);
+        self.contents = contents;
+        self.history = History::default();
+        self.modified = false;
+        self.saved_node = 0;
         Ok(())
     }
+
+    /// Replaces the contents with the file on disk. The reload is a single
+    /// undo step, so changes it throws away can be brought back. Buffers
+    /// longer than 'undoreload' lines start a new history instead.
+    pub fn reload(&mut self, options: &Options) -> io::Result<()> {
+        if self.explorer.is_some() {
+            return self.reload_explorer();
+        }
+        let contents = Buffer::from_reader(BufReader::new(File::open(&self.path)?))?;
+        if self.contents.len_lines() > options.undoreload {
+            self.contents = contents;
+            self.history = History::default();
+        } else {
+            // Only the part that differs goes into the history, a file that
+            // keeps growing would otherwise be copied into it on every reload.
+            let old = self.contents.to_string();
+            let new = contents.to_string();
+            let (old, new): (Vec<char>, Vec<char>) = (old.chars().collect(), new.chars().collect());
+            let start = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+            let end = old[start..]
+                .iter()
+                .rev()
+                .zip(new[start..].iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+            self.commit_undo_unit();
+            if start < old.len() - end {
+                self.remove(start..old.len() - end);
+            }
+            if start < new.len() - end {
+                self.insert(
+                    start,
+                    &new[start..new.len() - end].iter().collect::<String>(),
+                );
+            }
+            self.commit_undo_unit();
+        }
+        self.modified = false;
+        self.saved_node = self.history.current();
+        self.disk = Some(DiskState::new(&self.path, self.contents.content_hash())?);
+        Ok(())
+    }
+
+    /// Whether something else wrote to the file since it was read or last
+    /// saved. A file that's gone doesn't count, saving just brings it back.
+    pub fn changed_on_disk(&mut self) -> bool {
+        let Some(disk) = self.disk else {
+            return false;
+        };
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            return false;
+        };
+        if metadata.modified().ok() == disk.modified && metadata.len() == disk.len {
+            return false;
+        }
+        let Ok(contents) =
+            File::open(&self.path).and_then(|file| Buffer::from_reader(BufReader::new(file)))
+        else {
+            return false;
+        };
+        if contents.content_hash() != disk.hash {
+            return true;
+        }
+        // Only the time changed, remember it so the file isn't read again.
+        self.disk = DiskState::new(&self.path, disk.hash).ok();
+        false
+    }
+
+    /// The path relative to the working directory if it's inside it.
+    pub fn name(&self) -> String {
+        let relative = std::env::current_dir()
+            .ok()
+            .and_then(|directory| self.path.strip_prefix(directory).ok());
+        relative.unwrap_or(&self.path).to_string_lossy().to_string()
+    }
+
+    /// Writes the buffer to its file. A missing parent directory is only
+    /// created with `create_parents`, as `:w ++p` asks. A readonly buffer
+    /// or a file that changed on disk is only written with `force`, as `:w!`
+    /// asks.
+    pub fn save(&mut self, create_parents: bool, force: bool, options: &Options) -> io::Result<()> {
+        if self.explorer.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::IsADirectory,
+                "Is a directory",
+            ));
+        }
+        if self.scratch {
+            return Err(io::Error::other("is a scratch buffer"));
+        }
+        if self.readonly && !force {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "readonly option is set (add ! to override)",
+            ));
+        }
+        make_parent(&self.path, create_parents)?;
+        if !force && self.changed_on_disk() {
+            return Err(io::Error::other(
+                "has changed since reading it, :w! writes anyway",
+            ));
+        }
+        // Writing through a symlink replaces what it points to, not the link.
+        let path = match fs::symlink_metadata(&self.path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => fs::canonicalize(&self.path)?,
+            _ => self.path.clone(),
+        };
+        if options.backup && path.exists() {
+            backup(&path, options.backupdir.as_deref()).map_err(|error| {
+                io::Error::new(error.kind(), format!("Can't make backup file: {error}"))
+            })?;
+        }
+        // What's written is a state undo can come back to.
+        self.commit_undo_unit();
+        write_atomically(&path, force, |writer| self.contents.write_to(writer))?;
+        self.disk = DiskState::new(&path, self.contents.content_hash()).ok();
+
+        // The file itself is safe on disk at this point, losing the undo
+        // history to an unwritable cache dir shouldn't fail the save.
+        let _ = self.save_history();
+        self.is_new = false;
+        self.modified = false;
+        self.saved_node = self.history.current();
+        // As in vim, writing a readonly buffer with `!` makes it writable.
+        self.readonly = false;
+        Ok(())
+    }
+
+    /// Writes the buffer to another file, leaving the buffer's own file and
+    /// modified state alone. An existing file is only replaced with `force`.
+    pub fn write_copy(&self, path: &Path, create_parents: bool, force: bool) -> io::Result<()> {
+        if path.exists() && !force {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "File exists (add ! to override)",
+            ));
+        }
+        make_parent(path, create_parents)?;
+        write_atomically(path, force, |writer| self.contents.write_to(writer))
+    }
+
+    fn save_history(&self) -> io::Result<()> {
+        let Some(undo_path) = undo_file_path(&self.path) else {
+            return Ok(());
+        };
+        if let Some(parent) = undo_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut open = OpenOptions::new();
+        open.write(true).create(true).truncate(true);
+        let file = open.open(&undo_path)?;
+        // The history holds everything ever typed into the file, so it's no
+        // more readable than the file itself. Without one it's private.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&self.path).map_or(0o600, |metadata| {
+                metadata.permissions().mode() & 0o777 | 0o600
+            });
+            file.set_permissions(fs::Permissions::from_mode(mode))?;
+        }
+        self.history
+            .write_to(BufWriter::new(file), self.contents.content_hash())
+    }
+
+    fn load_history(path: &Path, contents: &Buffer) -> Option<History> {
+        let file = File::open(undo_file_path(path)?).ok()?;
+        History::read_from(BufReader::new(file), contents)
+    }
+
+    pub fn insert(&mut self, index: usize, text: &str) {
+        self.move_marks_for_insert(index, text);
+        self.contents.insert(index, text);
+        self.modified = true;
+        self.history.record(Edit::Insert {
+            index,
+            text: text.to_string(),
+        });
+    }
+
+    pub fn insert_char(&mut self, index: usize, char: char) {
+        self.move_marks_for_insert(index, char.encode_utf8(&mut [0; 4]));
+        self.contents.insert_char(index, char);
+        self.modified = true;
+        self.history.record(Edit::Insert {
+            index,
+            text: char.to_string(),
+        });
+    }
+
+    pub fn remove(&mut self, range: Range<usize>) {
+        let text = self.contents.slice(range.clone()).to_string();
+        self.move_marks_for_remove(range.clone(), &text);
+        self.contents.remove(range.clone());
+        self.modified = true;
+        self.history.record(Edit::Remove {
+            index: range.start,
+            text,
+        });
+    }
+
+    pub fn commit_undo_unit(&mut self) {
+        self.history.commit();
+    }
+
+    /// Text inserted before a marked line, or whole lines inserted where
+    /// it starts, move it down.
+    fn move_marks_for_insert(&mut self, index: usize, text: &str) {
+        let length = text.chars().count();
+        for mark in &mut self.marked_lines {
+            if *mark > index || (*mark == index && text.ends_with('\n')) {
+                *mark += length;
+            }
+        }
+    }
+
+    /// Marks move up with the text removed before them. A line that is
+    /// deleted or joined to the one above loses its mark.
+    fn move_marks_for_remove(&mut self, range: Range<usize>, text: &str) {
+        let at_line_start = range.start == 0 || self.contents.char(range.start - 1) == Some('\n');
+        let whole_lines = at_line_start && text.ends_with('\n');
+        self.marked_lines.retain_mut(|mark| match *mark {
+            mark if mark < range.start => true,
+            mark if mark == range.start => !text.contains('\n'),
+            mark if mark < range.end || (mark == range.end && !whole_lines) => false,
+            _ => {
+                *mark -= range.len();
+                true
+            }
+        });
+    }
+
+    pub fn undo(&mut self) -> Option<usize> {
+        let cursor = self.history.undo(&mut self.contents);
+        self.after_undo(cursor)
+    }
+
+    pub fn redo(&mut self) -> Option<usize> {
+        let cursor = self.history.redo(&mut self.contents);
+        self.after_undo(cursor)
+    }
+
+    pub fn earlier(&mut self) -> Option<usize> {
+        let cursor = self.history.earlier(&mut self.contents);
+        self.after_undo(cursor)
+    }
+
+    pub fn later(&mut self) -> Option<usize> {
+        let cursor = self.history.later(&mut self.contents);
+        self.after_undo(cursor)
+    }
+
+    /// The buffer is only modified if moving through the history left it
+    /// somewhere other than where the file was last read or written.
+    fn after_undo(&mut self, cursor: Option<usize>) -> Option<usize> {
+        if cursor.is_some() {
+            self.modified = self.history.current() != self.saved_node;
+        }
+        cursor
+    }
+}
+
+/// The one path a file is known by, so `x`, `./x` and `../dir/x` open the
+/// same buffer and share an undo file. Symlinks are followed as far as the
+/// path exists, and `.` and `..` are taken out of the rest.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return canonical;
+    }
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    let canonical_parent = normalized
+        .parent()
+        .zip(normalized.file_name())
+        .and_then(|(parent, name)| Some(fs::canonicalize(parent).ok()?.join(name)));
+    canonical_parent.unwrap_or(normalized)
+}
+
+/// Makes sure the directory `path` goes in exists. Only `create_parents`,
+/// as `:w ++p` asks, creates it.
+fn make_parent(path: &Path, create_parents: bool) -> io::Result<()> {
+    let Some(parent) = path.parent().filter(|parent| !parent.exists()) else {
+        return Ok(());
+    };
+    if !create_parents {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist, :w ++p creates it", parent.display()),
+        ));
+    }
+    fs::create_dir_all(parent)
+}
+
+/// Copies `path` to `name~` next to it, or into `directory` with the whole
+/// path in the name so files with the same name don't share a backup.
+fn backup(path: &Path, directory: Option<&Path>) -> io::Result<()> {
+    let backup_path = match directory {
+        Some(directory) => {
+            fs::create_dir_all(directory)?;
+            let name = path
+                .to_string_lossy()
+                .replace(std::path::MAIN_SEPARATOR, "%");
+            directory.join(format!("{name}~"))
+        }
+        None => {
+            let mut name = path.file_name().unwrap_or_default().to_os_string();
+            name.push("~");
+            path.with_file_name(name)
+        }
+    };
+    fs::copy(path, backup_path)?;
+    Ok(())
+}
+
+/// Writes to a temporary file in the same directory and renames it over
+/// `path` once it's synced, so a crash or a full disk leaves either the old
+/// file or the new one but never half of one. The old file's permissions
+/// and owner are kept where they can be. A read-only file is only replaced
+/// with `force`.
+fn write_atomically(
+    path: &Path,
+    force: bool,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = directory.join(format!(".{name}.jim-{}.tmp", std::process::id()));
+    let original = fs::metadata(path).ok();
+    // Renaming over a file only needs the directory to be writable, so the
+    // file's own permissions have to be checked here.
+    if !force
+        && original
+            .as_ref()
+            .is_some_and(|metadata| metadata.permissions().readonly())
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "is read-only (add ! to override)",
+        ));
+    }
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temporary)?;
+    let result = (|| {
+        // Before anything is written, so the text is never readable by more
+        // people than the old file allowed.
+        if let Some(metadata) = &original {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::{fchown, MetadataExt};
+                // Only root can give the file away, the group may still work.
+                let _ = fchown(&file, Some(metadata.uid()), Some(metadata.gid()))
+                    .or_else(|_| fchown(&file, None, Some(metadata.gid())));
+            }
+            file.set_permissions(metadata.permissions())?;
+        }
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        let file = writer.into_inner().map_err(|error| error.into_error())?;
+        file.sync_all()?;
+        fs::rename(&temporary, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+        return result;
+    }
+
+    // Syncing the directory makes the rename itself durable.
+    #[cfg(unix)]
+    if let Ok(directory) = File::open(directory) {
+        let _ = directory.sync_all();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own for a test.
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("jim-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        fs::canonicalize(directory).unwrap()
+    }
+
+    #[test]
+    fn normalize_path_gives_one_name_for_a_file() {
+        let directory = test_directory("normalize");
+        fs::create_dir(directory.join("sub")).unwrap();
+        fs::write(directory.join("sub/x"), "").unwrap();
+
+        let file = directory.join("sub/x");
+        assert_eq!(normalize_path(&directory.join("sub/./x")), file);
+        assert_eq!(normalize_path(&directory.join("sub/../sub/x")), file);
+        // Files that don't exist yet get the same treatment.
+        assert_eq!(
+            normalize_path(&directory.join("sub/../new")),
+            directory.join("new")
+        );
+        assert_eq!(
+            normalize_path(Path::new("/jim-missing/a/./../b")),
+            Path::new("/jim-missing/b")
+        );
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn reloading_records_only_what_changed() {
+        let directory = test_directory("reload");
+        let path = directory.join("log");
+        fs::write(&path, "a\nb\n").unwrap();
+        let mut file = JimFile::new(&path).unwrap();
+        fs::write(&path, "a\nb\nc\n").unwrap();
+        file.reload(&Options::default()).unwrap();
+        assert_eq!(file.contents.to_string(), "a\nb\nc\n");
+        assert!(!file.modified);
+
+        file.undo();
+        assert_eq!(file.contents.to_string(), "a\nb\n");
+        assert!(file.modified);
+        file.redo();
+        assert!(!file.modified);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn reloading_a_long_buffer_starts_a_new_history() {
+        let directory = test_directory("undoreload");
+        let path = directory.join("log");
+        fs::write(&path, "a\nb\n").unwrap();
+        let mut file = JimFile::new(&path).unwrap();
+        fs::write(&path, "c\n").unwrap();
+        let options = Options {
+            undoreload: 1,
+            ..Options::default()
+        };
+        file.reload(&options).unwrap();
+        assert_eq!(file.contents.to_string(), "c\n");
+        assert_eq!(file.undo(), None);
+        assert_eq!(file.contents.to_string(), "c\n");
+        fs::remove_dir_all(directory).unwrap();
+    }
 }
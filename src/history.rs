@@ -0,0 +1,516 @@
+use std::{
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::buffer::Buffer;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    Insert { index: usize, text: String },
+    Remove { index: usize, text: String },
+}
+
+impl Edit {
+    pub fn apply(&self, buffer: &mut Buffer) {
+        match self {
+            Edit::Insert { index, text } => buffer.insert(*index, text),
+            Edit::Remove { index, text } => buffer.remove(*index..*index + text.chars().count()),
+        }
+    }
+
+    /// Applies the edit if it fits `buffer`: the text it removes has to be
+    /// there and where it inserts has to be inside the buffer.
+    fn try_apply(&self, buffer: &mut Buffer) -> bool {
+        let fits = match self {
+            Edit::Insert { index, .. } => *index <= buffer.len_chars(),
+            Edit::Remove { index, text } => {
+                let end = index + text.chars().count();
+                end <= buffer.len_chars() && buffer.slice(*index..end) == text.as_str()
+            }
+        };
+        if fits {
+            self.apply(buffer);
+        }
+        fits
+    }
+
+    pub fn inverse(&self) -> Edit {
+        match self {
+            Edit::Insert { index, text } => Edit::Remove {
+                index: *index,
+                text: text.clone(),
+            },
+            Edit::Remove { index, text } => Edit::Insert {
+                index: *index,
+                text: text.clone(),
+            },
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        match self {
+            Edit::Insert { index, .. } | Edit::Remove { index, .. } => *index,
+        }
+    }
+
+    /// Folds `next` into `self` when it continues the same run of typing or
+    /// deleting, so an insert session doesn't store one edit per key.
+    fn merge(&mut self, next: &Edit) -> bool {
+        match (self, next) {
+            (
+                Edit::Insert { index, text },
+                Edit::Insert {
+                    index: next_index,
+                    text: next_text,
+                },
+            ) if *index + text.chars().count() == *next_index => {
+                text.push_str(next_text);
+                true
+            }
+            (
+                Edit::Remove { index, text },
+                Edit::Remove {
+                    index: next_index,
+                    text: next_text,
+                },
+            ) if *next_index + next_text.chars().count() == *index => {
+                text.insert_str(0, next_text);
+                *index = *next_index;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A group of edits that is undone and redone as a whole.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UndoUnit {
+    pub edits: Vec<Edit>,
+}
+
+impl UndoUnit {
+    /// Where the cursor goes after undoing or redoing this unit.
+    pub fn cursor(&self) -> usize {
+        self.edits.first().map(Edit::index).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct UndoNode {
+    parent: usize,
+    /// The child `redo` moves to, the branch most recently created or left.
+    redo_child: Option<usize>,
+    unit: UndoUnit,
+}
+
+/// Undo tree, node 0 is the unchanged file and every other node holds the
+/// unit that leads to it from its parent. Node indices are chronological.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    nodes: Vec<UndoNode>,
+    current: usize,
+    pending: UndoUnit,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            nodes: vec![UndoNode::default()],
+            current: 0,
+            pending: UndoUnit::default(),
+        }
+    }
+}
+
+impl History {
+    pub fn record(&mut self, edit: Edit) {
+        if let Some(last) = self.pending.edits.last_mut() {
+            if last.merge(&edit) {
+                return;
+            }
+        }
+        self.pending.edits.push(edit);
+    }
+
+    /// The node the buffer is at, the text matches it once `commit`ted.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Closes the current undo unit, if anything was recorded into it.
+    pub fn commit(&mut self) {
+        if self.pending.edits.is_empty() {
+            return;
+        }
+        self.nodes.push(UndoNode {
+            parent: self.current,
+            redo_child: None,
+            unit: std::mem::take(&mut self.pending),
+        });
+        let node = self.nodes.len() - 1;
+        self.nodes[self.current].redo_child = Some(node);
+        self.current = node;
+    }
+
+    /// Reverts the current undo unit, returning where the cursor should go.
+    pub fn undo(&mut self, buffer: &mut Buffer) -> Option<usize> {
+        self.commit();
+        if self.current == 0 {
+            return None;
+        }
+        Some(self.undo_node(buffer))
+    }
+
+    /// Reapplies the last undone unit, returning where the cursor should go.
+    pub fn redo(&mut self, buffer: &mut Buffer) -> Option<usize> {
+        self.commit();
+        let child = self.nodes[self.current].redo_child?;
+        Some(self.redo_node(child, buffer))
+    }
+
+    /// Moves to the chronologically previous state, like vim's `g-`.
+    pub fn earlier(&mut self, buffer: &mut Buffer) -> Option<usize> {
+        self.commit();
+        let target = self.current.checked_sub(1)?;
+        self.goto(target, buffer)
+    }
+
+    /// Moves to the chronologically next state, like vim's `g+`.
+    pub fn later(&mut self, buffer: &mut Buffer) -> Option<usize> {
+        self.commit();
+        let target = self.current + 1;
+        if target >= self.nodes.len() {
+            return None;
+        }
+        self.goto(target, buffer)
+    }
+
+    fn goto(&mut self, target: usize, buffer: &mut Buffer) -> Option<usize> {
+        let target_path = self.path_to_root(target);
+        let mut cursor = None;
+        while !target_path.contains(&self.current) {
+            cursor = Some(self.undo_node(buffer));
+        }
+        let common = target_path.iter().position(|&node| node == self.current)?;
+        for &node in target_path[..common].iter().rev() {
+            cursor = Some(self.redo_node(node, buffer));
+        }
+        cursor
+    }
+
+    fn path_to_root(&self, mut node: usize) -> Vec<usize> {
+        let mut path = vec![node];
+        while node != 0 {
+            node = self.nodes[node].parent;
+            path.push(node);
+        }
+        path
+    }
+
+    fn undo_node(&mut self, buffer: &mut Buffer) -> usize {
+        let node = &self.nodes[self.current];
+        for edit in node.unit.edits.iter().rev() {
+            edit.inverse().apply(buffer);
+        }
+        let cursor = node.unit.cursor();
+        let (parent, child) = (node.parent, self.current);
+        self.nodes[parent].redo_child = Some(child);
+        self.current = parent;
+        cursor
+    }
+
+    fn redo_node(&mut self, node: usize, buffer: &mut Buffer) -> usize {
+        for edit in &self.nodes[node].unit.edits {
+            edit.apply(buffer);
+        }
+        self.nodes[self.current].redo_child = Some(node);
+        self.current = node;
+        self.nodes[node].unit.cursor()
+    }
+
+    /// Writes the committed tree, tagged with the hash of the text it ends
+    /// at so a later load can tell if the file changed without it.
+    pub fn write_to<W: Write>(&self, mut writer: W, content_hash: u64) -> io::Result<()> {
+        writeln!(writer, "{UNDO_FILE_MAGIC}")?;
+        writeln!(writer, "hash {content_hash:016x}")?;
+        writeln!(writer, "current {}", self.current)?;
+        for node in &self.nodes[1..] {
+            let redo_child = node.redo_child.map_or(-1, |child| child as i64);
+            writeln!(
+                writer,
+                "node {} {} {}",
+                node.parent,
+                redo_child,
+                node.unit.edits.len()
+            )?;
+            for edit in &node.unit.edits {
+                let (kind, index, text) = match edit {
+                    Edit::Insert { index, text } => ('+', index, text),
+                    Edit::Remove { index, text } => ('-', index, text),
+                };
+                writeln!(writer, "{kind} {index} {}", text.len())?;
+                writer.write_all(text.as_bytes())?;
+                writeln!(writer)?;
+            }
+        }
+        writer.flush()
+    }
+
+    /// Reads a tree written by `write_to`, returning `None` if it is
+    /// malformed or was saved for other contents than `contents`.
+    pub fn read_from<R: BufRead>(mut reader: R, contents: &Buffer) -> Option<Self> {
+        let mut line = String::new();
+        let mut next_line = |reader: &mut R| -> Option<String> {
+            line.clear();
+            reader.read_line(&mut line).ok()?;
+            Some(line.trim_end_matches('\n').to_string())
+        };
+
+        if next_line(&mut reader)? != UNDO_FILE_MAGIC {
+            return None;
+        }
+        let hash = u64::from_str_radix(next_line(&mut reader)?.strip_prefix("hash ")?, 16).ok()?;
+        if hash != contents.content_hash() {
+            return None;
+        }
+        let current = next_line(&mut reader)?
+            .strip_prefix("current ")?
+            .parse()
+            .ok()?;
+
+        let mut history = History::default();
+        loop {
+            let header = next_line(&mut reader)?;
+            if header.is_empty() {
+                break;
+            }
+            let mut fields = header.strip_prefix("node ")?.split(' ');
+            let parent: usize = fields.next()?.parse().ok()?;
+            let redo_child: i64 = fields.next()?.parse().ok()?;
+            let edit_count: usize = fields.next()?.parse().ok()?;
+
+            let mut unit = UndoUnit::default();
+            for _ in 0..edit_count {
+                let edit_header = next_line(&mut reader)?;
+                let mut fields = edit_header.split(' ');
+                let kind = fields.next()?;
+                let index = fields.next()?.parse().ok()?;
+                let length: usize = fields.next()?.parse().ok()?;
+
+                let mut text = vec![0; length + 1];
+                reader.read_exact(&mut text).ok()?;
+                text.pop();
+                let text = String::from_utf8(text).ok()?;
+                unit.edits.push(match kind {
+                    "+" => Edit::Insert { index, text },
+                    "-" => Edit::Remove { index, text },
+                    _ => return None,
+                });
+            }
+
+            history.nodes.push(UndoNode {
+                parent,
+                redo_child: usize::try_from(redo_child).ok(),
+                unit,
+            });
+        }
+
+        // Nodes are chronological, so every parent comes before its children.
+        // Anything else could send `path_to_root` around in a cycle.
+        let node_count = history.nodes.len();
+        let in_order = |(index, node): (usize, &UndoNode)| {
+            (index == 0 || node.parent < index)
+                && node
+                    .redo_child
+                    .is_none_or(|child| index < child && child < node_count)
+        };
+        if current >= node_count || !history.nodes.iter().enumerate().all(in_order) {
+            return None;
+        }
+        history.nodes[0].redo_child = history
+            .nodes
+            .iter()
+            .rposition(|node| node.parent == 0)
+            .filter(|&node| node != 0);
+        history.current = current;
+        history.fits(contents).then_some(history)
+    }
+
+    /// Whether every unit in the tree applies cleanly, going through it on a
+    /// copy of `contents`. An undo file can match the hash and still be cut
+    /// short or edited by hand, and `undo` would panic on a bad edit.
+    fn fits(&self, contents: &Buffer) -> bool {
+        let mut buffer = contents.clone();
+        let undo = |node: usize, buffer: &mut Buffer| {
+            let edits = &self.nodes[node].unit.edits;
+            edits
+                .iter()
+                .rev()
+                .all(|edit| edit.inverse().try_apply(buffer))
+        };
+        let redo = |node: usize, buffer: &mut Buffer| {
+            let edits = &self.nodes[node].unit.edits;
+            edits.iter().all(|edit| edit.try_apply(buffer))
+        };
+
+        let path = self.path_to_root(self.current);
+        if !path[..path.len() - 1]
+            .iter()
+            .all(|&node| undo(node, &mut buffer))
+        {
+            return false;
+        }
+        let mut children = vec![vec![]; self.nodes.len()];
+        for (node, UndoNode { parent, .. }) in self.nodes.iter().enumerate().skip(1) {
+            children[*parent].push(node);
+        }
+        // Down into each node and back up again, without recursing since a
+        // long history is a deep tree.
+        let mut stack: Vec<(usize, bool)> = children[0].iter().map(|&node| (node, true)).collect();
+        while let Some((node, entering)) = stack.pop() {
+            if !entering {
+                if !undo(node, &mut buffer) {
+                    return false;
+                }
+                continue;
+            }
+            if !redo(node, &mut buffer) {
+                return false;
+            }
+            stack.push((node, false));
+            stack.extend(children[node].iter().map(|&child| (child, true)));
+        }
+        true
+    }
+}
+
+const UNDO_FILE_MAGIC: &str = "jim-undo 1";
+
+/// Where the undo tree for `path` is cached, one flat file per path with
+/// its separators escaped like vim's `undodir`.
+pub fn undo_file_path(path: &Path) -> Option<PathBuf> {
+    let cache_dir = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+    let name = path.to_string_lossy().replace('%', "%%").replace('/', "%");
+    Some(cache_dir.join("jim").join("undo").join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records `edit` on `buffer` as a unit of its own.
+    fn edit(history: &mut History, buffer: &mut Buffer, edit: Edit) {
+        edit.apply(buffer);
+        history.record(edit);
+        history.commit();
+    }
+
+    fn insert(index: usize, text: &str) -> Edit {
+        Edit::Insert {
+            index,
+            text: text.to_string(),
+        }
+    }
+
+    fn remove(index: usize, text: &str) -> Edit {
+        Edit::Remove {
+            index,
+            text: text.to_string(),
+        }
+    }
+
+    /// A tree with a branch: "ab" is undone and "é\n" typed instead.
+    fn branching_history() -> (History, Buffer) {
+        let mut history = History::default();
+        let mut buffer = Buffer::from("x\n");
+        edit(&mut history, &mut buffer, insert(0, "ab"));
+        edit(&mut history, &mut buffer, remove(1, "b"));
+        history.undo(&mut buffer);
+        history.undo(&mut buffer);
+        edit(&mut history, &mut buffer, insert(2, "é\n"));
+        (history, buffer)
+    }
+
+    fn written(history: &History, buffer: &Buffer) -> Vec<u8> {
+        let mut file = vec![];
+        history.write_to(&mut file, buffer.content_hash()).unwrap();
+        file
+    }
+
+    #[test]
+    fn undo_file_round_trips() {
+        let (history, buffer) = branching_history();
+        assert_eq!(buffer.to_string(), "x\né\n");
+        let file = written(&history, &buffer);
+        let read = History::read_from(file.as_slice(), &buffer).unwrap();
+        assert_eq!(read, history);
+
+        let mut buffer = buffer;
+        let mut read = read;
+        read.earlier(&mut buffer);
+        assert_eq!(buffer.to_string(), "ax\n");
+        read.earlier(&mut buffer);
+        assert_eq!(buffer.to_string(), "abx\n");
+        read.undo(&mut buffer);
+        assert_eq!(buffer.to_string(), "x\n");
+    }
+
+    #[test]
+    fn undo_file_for_other_contents_is_ignored() {
+        let (history, buffer) = branching_history();
+        let file = written(&history, &buffer);
+        assert_eq!(
+            History::read_from(file.as_slice(), &Buffer::from("y\n")),
+            None
+        );
+    }
+
+    #[test]
+    fn truncated_undo_file_is_rejected() {
+        let (history, buffer) = branching_history();
+        let file = written(&history, &buffer);
+        for length in 0..file.len() - 1 {
+            assert_eq!(History::read_from(&file[..length], &buffer), None);
+        }
+    }
+
+    #[test]
+    fn undo_file_with_edits_outside_the_buffer_is_rejected() {
+        let (history, buffer) = branching_history();
+        let file = String::from_utf8(written(&history, &buffer)).unwrap();
+        for (from, to) in [("+ 0 2", "+ 9 2"), ("- 1 1", "- 7 1"), ("\nb\n", "\nz\n")] {
+            let edited = file.replacen(from, to, 1);
+            assert_ne!(edited, file);
+            assert_eq!(History::read_from(edited.as_bytes(), &buffer), None);
+        }
+    }
+
+    #[test]
+    fn undo_file_with_a_parent_after_its_child_is_rejected() {
+        let (history, buffer) = branching_history();
+        let file = String::from_utf8(written(&history, &buffer)).unwrap();
+        let edited = file.replacen("node 0 ", "node 2 ", 1);
+        assert_eq!(History::read_from(edited.as_bytes(), &buffer), None);
+    }
+
+    #[test]
+    fn typing_merges_into_one_edit() {
+        let mut history = History::default();
+        let mut buffer = Buffer::new();
+        for (index, char) in "abc".chars().enumerate() {
+            let typed = insert(index, &char.to_string());
+            typed.apply(&mut buffer);
+            history.record(typed);
+        }
+        history.commit();
+        assert_eq!(history.nodes[1].unit.edits, vec![insert(0, "abc")]);
+        assert_eq!(history.undo(&mut buffer), Some(0));
+        assert_eq!(buffer.to_string(), "");
+        assert_eq!(history.undo(&mut buffer), None);
+    }
+}
@@ -1,20 +1,40 @@
 use crate::{
+    buffer::Buffer,
+    cli::{Args, Start},
+    command_line::{self, ArgumentKind, Browse, CommandLine, Completion, LineHistory},
     cursor::Cursor,
-    file::JimFile,
-    mapping::{Command, Mapping},
+    diff,
+    ex::{self, ExCommand, LineRange},
+    explorer::Explorer,
+    file::{self, JimFile},
+    mapping::{normalize_key, Command, CommandAction, KeyMatch, MapContext, Mapping},
+    message::Message,
     mode::Mode,
+    motion::MotionKind,
+    operator::{BlockInsert, Operator},
+    options::Options,
+    register::{Register, Registers},
+    search::{self, Search},
+    selection::{Selection, SelectionKind},
+    substitute::{Substitute, Substitution},
+    watcher::FileWatcher,
+    window::{self, ScreenLine, Side, Split, Windows},
 };
-use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
 use nalgebra::Vector2;
+use ropey::RopeSlice;
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
     io,
-    path::{Path, PathBuf},
+    ops::Range,
+    path::Path,
+    time::Duration,
 };
 use tui::{
     backend::Backend,
-    layout::{Constraint, Direction, Layout},
-    text::Text,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans, Text},
     widgets::Paragraph,
     Frame, Terminal,
 };
@@ -22,30 +42,73 @@ use tui::{
 #[derive(Debug, Default)]
 pub struct Jim {
     properties: JimProperties,
-    command: String,
+    command: CommandLine,
+    /// Going through the history with `<Up>` and `<Down>` at a prompt.
+    browse: Option<Browse>,
+    /// Going through completions with `<Tab>` at the `:` prompt.
+    completion: Option<Completion>,
+    count: Option<usize>,
+    register: Option<char>,
+    awaiting_register: bool,
     nmaps: Vec<Mapping>,
     imaps: Vec<Mapping>,
+    vmaps: Vec<Mapping>,
+    omaps: Vec<Mapping>,
     cmaps: Vec<Command>,
+    /// What `diff_lines` found last, by the buffers compared.
+    diffs: HashMap<(usize, usize), Diff>,
+}
+
+/// The lines that differ between two buffers at the revisions compared.
+#[derive(Debug)]
+struct Diff {
+    revisions: (u64, u64),
+    changed: Vec<bool>,
 }
 
 #[derive(Debug, Default)]
 pub struct JimProperties {
     pub mode: Mode,
     pub cursor: Cursor,
+    pub visual_anchor: Cursor,
     pub quitting: bool,
-    pub buttons_pressed: Vec<KeyCode>,
+    pub buttons_pressed: Vec<KeyEvent>,
     pub buffers: Vec<JimFile>,
     pub recent_buffers: VecDeque<usize>,
-    pub cant_press_maps: bool,
+    pub pending_operator: Option<Operator>,
+    pub operator_context: MapContext,
+    /// A visual-block `c` being typed.
+    pub block_insert: Option<BlockInsert>,
+    pub registers: Registers,
+    pub message: Option<Message>,
+    /// Messages shown so far, the oldest first, for `:messages`.
+    pub messages: VecDeque<Message>,
+    pub windows: Windows,
+    pub options: Options,
+    /// Waiting for a one key answer to the question in `message`.
+    pub prompt: Option<fn(&mut JimProperties, char)>,
+    /// Commands typed at the `:` prompt.
+    pub command_history: LineHistory,
+    /// The last `:s`, for `&` and `:&&`.
+    pub last_substitute: Option<Substitute>,
+    /// A `:s///c` waiting for an answer.
+    substitution: Option<Substitution>,
+    pub search: Search,
+    /// The `/` or `?` being typed.
+    search_prompt: Option<search::Prompt>,
+    /// Running the command of a `:g`, its changes undo as one.
+    in_global: bool,
+    /// `None` when the system has no way to watch files.
+    pub watcher: Option<FileWatcher>,
 }
 
-impl JimProperties {
-    pub fn use_mapping(&mut self, buttons: &Vec<KeyCode>) -> bool {
-        self.buttons_pressed
-            .windows(buttons.len())
-            .any(|window| window == buttons)
-    }
+/// How many messages `:messages` remembers.
+const MESSAGE_HISTORY: usize = 200;
 
+/// How long to wait for a key before looking at the watcher again.
+const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+impl JimProperties {
     pub fn move_cursor_down(&mut self, amount: usize) {
         for _ in 0..amount {
             self.cursor.move_down(&self.buffers[self.recent_buffers[0]])
@@ -71,11 +134,10 @@ impl JimProperties {
         }
     }
 
-    pub fn current_line(&self) -> &str {
+    pub fn current_line(&self) -> RopeSlice<'_> {
         self.buffers[self.recent_buffers[0]]
             .contents
-            .lines()
-            .nth(self.cursor.xy_pos.y)
+            .line(self.cursor.xy_pos.y)
             .unwrap()
     }
 
@@ -83,7 +145,7 @@ impl JimProperties {
         &self.buffers[self.recent_buffers[0]]
     }
 
-    pub fn get_current_buffer_contents(&self) -> &String {
+    pub fn get_current_buffer_contents(&self) -> &Buffer {
         &self.get_current_buffer().contents
     }
 
@@ -91,26 +153,1025 @@ impl JimProperties {
         &mut self.buffers[self.recent_buffers[0]]
     }
 
-    pub fn get_mut_current_buffer_contents(&mut self) -> &mut String {
+    pub fn get_mut_current_buffer_contents(&mut self) -> &mut Buffer {
         &mut self.get_mut_current_buffer().contents
     }
 
+    pub fn set_cursor_index(&mut self, index: usize) {
+        self.cursor
+            .set_index(index, &self.buffers[self.recent_buffers[0]]);
+    }
+
+    /// The visual mode selection, or the match a search being typed goes
+    /// to. `None` outside of those.
+    pub fn selection(&self) -> Option<Selection> {
+        if self.mode == Mode::Search {
+            let preview = self.search_prompt.as_ref()?.preview.clone()?;
+            return Some(Selection::characterwise(preview, self.get_current_buffer()));
+        }
+        let kind = self.mode.selection_kind()?;
+        Some(Selection::new(
+            kind,
+            &self.visual_anchor,
+            &self.cursor,
+            self.get_current_buffer(),
+        ))
+    }
+
+    /// Starts waiting for the motion or text object `operator` acts on.
+    pub fn begin_operator(&mut self, operator: Operator, context: MapContext) {
+        self.pending_operator = Some(operator);
+        self.operator_context = context;
+        self.visual_anchor = self.cursor;
+        self.mode = Mode::OperatorPending;
+    }
+
+    pub fn cancel_operator(&mut self) {
+        self.pending_operator = None;
+        self.operator_context = MapContext::default();
+        self.mode = Mode::Normal;
+    }
+
+    /// Applies the pending operator from where it started to the cursor.
+    pub fn apply_operator_to_motion(&mut self, kind: MotionKind) {
+        let Some(operator) = self.pending_operator.take() else {
+            return;
+        };
+        let register = std::mem::take(&mut self.operator_context).register;
+        let (anchor, cursor) = (self.visual_anchor, self.cursor);
+        if kind != MotionKind::Linewise && anchor.index == cursor.index {
+            self.cursor = anchor;
+            self.cancel_operator();
+            return;
+        }
+
+        let file = self.get_current_buffer();
+        let selection = match kind {
+            MotionKind::Linewise => Selection::new(SelectionKind::Linewise, &anchor, &cursor, file),
+            MotionKind::Inclusive => {
+                Selection::new(SelectionKind::Characterwise, &anchor, &cursor, file)
+            }
+            MotionKind::Exclusive => {
+                let (start, end) = if anchor.index < cursor.index {
+                    (anchor, cursor)
+                } else {
+                    (cursor, anchor)
+                };
+                // An exclusive motion that ends at the start of a later line
+                // stops at the end of the line before, so `dw` on the last
+                // word of a line keeps the line break.
+                let mut end_index = end.index;
+                if end.xy_pos.y > start.xy_pos.y && end.get_position(file).x == 0 {
+                    end_index = file.contents.line_to_char(end.xy_pos.y) - 1;
+                }
+                if end_index <= start.index {
+                    self.cancel_operator();
+                    return;
+                }
+                Selection::characterwise(start.index..end_index, file)
+            }
+        };
+        operator.apply(self, selection, register);
+    }
+
+    /// Selects `range` in visual mode, or applies the pending operator to it.
+    pub fn select_text_object(&mut self, range: Range<usize>) {
+        if self.mode.is_visual() {
+            if !range.is_empty() {
+                self.set_cursor_index(range.start);
+                self.visual_anchor = self.cursor;
+                self.set_cursor_index(range.end - 1);
+            }
+            return;
+        }
+
+        let Some(operator) = self.pending_operator.take() else {
+            return;
+        };
+        let register = std::mem::take(&mut self.operator_context).register;
+        if range.is_empty() {
+            self.cancel_operator();
+            // `ci(` on `()` still leaves you typing between the brackets.
+            if operator == Operator::Change {
+                self.set_cursor_index(range.start);
+                self.mode = Mode::Insert;
+            }
+            return;
+        }
+        let selection = Selection::characterwise(range, self.get_current_buffer());
+        operator.apply(self, selection, register);
+    }
+
+    /// Reads a register, including `%` which the registers don't know.
+    pub fn read_register(&self, name: char) -> Option<Register> {
+        if name == '%' {
+            let path = self.get_current_buffer().path.to_string_lossy().to_string();
+            return Some(Register::new(path, SelectionKind::Characterwise));
+        }
+        self.registers.get(name)
+    }
+
+    /// Puts the contents of `name` after the cursor, or before it if
+    /// `before` is set, `count` times.
+    pub fn put(&mut self, name: Option<char>, before: bool, count: usize) {
+        let Some(register) = self.read_register(name.unwrap_or('"')) else {
+            return;
+        };
+        let position = self.cursor.get_position(self.get_current_buffer());
+        let contents = self.get_current_buffer_contents();
+        let line_length = contents.line_len(position.y).unwrap_or_default();
+
+        match register.kind {
+            SelectionKind::Characterwise => {
+                let mut index = self.cursor.index;
+                if !before && line_length > 0 {
+                    index += 1;
+                }
+                let text = register.text.repeat(count);
+                self.get_mut_current_buffer().insert(index, &text);
+                self.set_cursor_index(index + text.chars().count().saturating_sub(1));
+            }
+            SelectionKind::Linewise => {
+                let line = if before { position.y } else { position.y + 1 };
+                let mut text = register.text.repeat(count);
+                let index = contents.line_to_char(line);
+                // Putting below a last line that has no line break needs one
+                // before the new lines instead of after them.
+                if line >= contents.len_lines() && !contents.ends_with_line_break() {
+                    text.pop();
+                    text.insert(0, '\n');
+                    self.get_mut_current_buffer().insert(index, &text);
+                    self.set_cursor_index(index + 1);
+                } else {
+                    self.get_mut_current_buffer().insert(index, &text);
+                    self.set_cursor_index(index);
+                }
+            }
+            SelectionKind::Blockwise => {
+                let column = if !before && line_length > 0 {
+                    position.x + 1
+                } else {
+                    position.x
+                };
+                for (offset, block_line) in register.text.split('\n').enumerate() {
+                    let line = position.y + offset;
+                    let buffer = self.get_mut_current_buffer();
+                    if line >= buffer.contents.len_lines() {
+                        let end = buffer.contents.len_chars();
+                        buffer.insert(end, "\n");
+                    }
+                    let length = buffer.contents.line_len(line).unwrap_or_default();
+                    let line_start = buffer.contents.line_to_char(line);
+                    let padding = " ".repeat(column.saturating_sub(length));
+                    let text = padding + &block_line.repeat(count);
+                    buffer.insert(line_start + column.min(length), &text);
+                }
+                let index = self.get_current_buffer_contents().line_to_char(position.y) + column;
+                self.set_cursor_index(index);
+            }
+        }
+    }
+
+    /// The lines a command's range covers counted from 0, the cursor line
+    /// without one.
+    pub fn command_lines(&self, range: Option<LineRange>) -> Result<(usize, usize), String> {
+        let current = self.cursor.xy_pos.y;
+        match range {
+            Some(range) => range.resolve(current, self.get_current_buffer_contents().len_lines()),
+            None => Ok((current, current)),
+        }
+    }
+
+    /// Starts typing a `/` search, or a `?` one when `backward`.
+    pub fn begin_search(&mut self, backward: bool, context: MapContext) {
+        self.search_prompt = Some(search::Prompt {
+            backward,
+            count: context.count(),
+            origin: self.cursor,
+            mode: self.mode,
+            preview: None,
+            haystack: search::Haystack::new(self.get_current_buffer_contents()),
+        });
+        self.mode = Mode::Search;
+    }
+
+    /// Moves to where `pattern` would go while it's being typed.
+    pub fn preview_search(&mut self, pattern: &str) {
+        let Some(prompt) = &self.search_prompt else {
+            return;
+        };
+        let (origin, backward) = (prompt.origin, prompt.backward);
+        let found = match self.options.incsearch && !pattern.is_empty() {
+            true => search::regex(pattern, &self.options, true)
+                .ok()
+                .and_then(|regex| {
+                    prompt.haystack.find(
+                        self.get_current_buffer_contents(),
+                        &regex,
+                        origin.index,
+                        backward,
+                        self.options.wrapscan,
+                    )
+                }),
+            false => None,
+        };
+        self.cursor = origin;
+        if let Some(found) = &found {
+            self.set_cursor_index(found.range.start);
+        }
+        if let Some(prompt) = &mut self.search_prompt {
+            prompt.preview = found
+                .map(|found| found.range)
+                .filter(|range| !range.is_empty());
+        }
+    }
+
+    /// Searches for the `pattern` typed, the last one again if it's empty.
+    /// A search typed after an operator is its motion.
+    pub fn finish_search(&mut self, pattern: &str) -> Result<(), String> {
+        let Some(prompt) = self.search_prompt.take() else {
+            return Ok(());
+        };
+        self.cursor = prompt.origin;
+        self.mode = prompt.mode;
+        if !pattern.is_empty() {
+            self.search.history.remember(pattern);
+            self.search.pattern = Some(pattern.to_string());
+            self.search.smartcase = true;
+        }
+        self.search.backward = prompt.backward;
+        let result = self.search_next(prompt.count, false);
+        if self.mode == Mode::OperatorPending {
+            match result {
+                Ok(()) => self.apply_operator_to_motion(MotionKind::Exclusive),
+                Err(_) => self.cancel_operator(),
+            }
+        }
+        result
+    }
+
+    /// Gives up on the search being typed and goes back to where it began.
+    pub fn cancel_search(&mut self) {
+        let Some(prompt) = self.search_prompt.take() else {
+            return;
+        };
+        self.cursor = prompt.origin;
+        self.mode = prompt.mode;
+        if self.mode == Mode::OperatorPending {
+            self.cancel_operator();
+        }
+    }
+
+    /// Searches for the word under the cursor like `*`, or backwards like
+    /// `#`. Only whole words match.
+    pub fn search_word(&mut self, backward: bool, count: usize) -> Result<(), String> {
+        let contents = self.get_current_buffer_contents();
+        let (start, word) =
+            search::word_at(contents, self.cursor.index).ok_or("No string under cursor")?;
+        let pattern = format!(r"\b{}\b", regex::escape(&word));
+        self.search.history.remember(&pattern);
+        self.search.pattern = Some(pattern);
+        self.search.backward = backward;
+        self.search.smartcase = false;
+        // Backwards the word itself would be the first match.
+        if backward {
+            self.set_cursor_index(start);
+        }
+        self.search_next(count, false)
+    }
+
+    /// Goes to the `count`th match of the last search, like `n`, or the
+    /// other way like `N` when `reverse`.
+    pub fn search_next(&mut self, count: usize, reverse: bool) -> Result<(), String> {
+        let pattern = self
+            .search
+            .pattern
+            .clone()
+            .ok_or("No previous regular expression")?;
+        let backward = self.search.backward != reverse;
+        let regex = search::regex(&pattern, &self.options, self.search.smartcase)?;
+        let contents = self.get_current_buffer_contents();
+        let haystack = search::Haystack::new(contents);
+        let (mut index, mut wrapped) = (self.cursor.index, false);
+        for _ in 0..count {
+            let found = haystack.find(contents, &regex, index, backward, self.options.wrapscan);
+            let Some(found) = found else {
+                return Err(match (self.options.wrapscan, backward) {
+                    (true, _) => format!("Pattern not found: {pattern}"),
+                    (false, false) => format!("search hit BOTTOM without match for: {pattern}"),
+                    (false, true) => format!("search hit TOP without match for: {pattern}"),
+                });
+            };
+            index = found.range.start;
+            wrapped |= found.wrapped;
+        }
+        self.set_cursor_index(index);
+        if wrapped {
+            self.show(Message::warning(match backward {
+                false => "search hit BOTTOM, continuing at TOP",
+                true => "search hit TOP, continuing at BOTTOM",
+            }));
+        }
+        Ok(())
+    }
+
+    /// Runs `substitute` over the lines `first..=last` as one undo step.
+    /// With the `c` flag it stops at each match to ask first.
+    pub fn substitute(
+        &mut self,
+        substitute: Substitute,
+        first: usize,
+        last: usize,
+    ) -> Result<(), String> {
+        // `:g` runs its command on every line before anything could be
+        // answered, so only the last line would get asked about.
+        if self.in_global && substitute.flags.confirm {
+            return Err("Cannot ask for confirmation inside :global".to_string());
+        }
+        let substitution = Substitution::new(&substitute, &self.options, first, last)?;
+        self.search.pattern = Some(substitute.pattern.clone());
+        self.search.smartcase = true;
+        self.last_substitute = Some(substitute);
+        self.commit_undo_unit();
+        self.continue_substitution(substitution);
+        Ok(())
+    }
+
+    fn continue_substitution(&mut self, mut substitution: Substitution) {
+        let buffer = &mut self.buffers[self.recent_buffers[0]];
+        while let Some(found) = substitution.next_match(buffer) {
+            if !substitution.confirm() {
+                substitution.replace(buffer, &found);
+                continue;
+            }
+            self.set_cursor_index(found.range.start);
+            self.message = Some(Message::info(format!(
+                "replace with {} (y/n/a/q/l)?",
+                found.replacement
+            )));
+            substitution.pending = Some(found);
+            self.substitution = Some(substitution);
+            self.prompt = Some(Self::answer_substitution);
+            return;
+        }
+        self.finish_substitution(substitution);
+    }
+
+    /// `y` replaces the match, `n` skips it, `a` replaces it and the rest
+    /// without asking, `l` replaces it and stops and anything else stops.
+    fn answer_substitution(&mut self, answer: char) {
+        let Some(mut substitution) = self.substitution.take() else {
+            return;
+        };
+        let Some(found) = substitution.pending.take() else {
+            return;
+        };
+        let buffer = &mut self.buffers[self.recent_buffers[0]];
+        match answer {
+            'y' | 'a' | 'l' => substitution.replace(buffer, &found),
+            'n' => substitution.skip(&found, buffer),
+            _ => {}
+        }
+        match answer {
+            'y' | 'n' => self.continue_substitution(substitution),
+            'a' => {
+                substitution.stop_confirming();
+                self.continue_substitution(substitution);
+            }
+            _ => self.finish_substitution(substitution),
+        }
+    }
+
+    /// Ends the undo step and puts the cursor on the last changed line.
+    /// Like vim, only more than two substitutions are worth a message.
+    fn finish_substitution(&mut self, substitution: Substitution) {
+        self.commit_undo_unit();
+        // `:g/a/s/b/c/` only complains if no line had a `b` at all.
+        if !substitution.matched && self.in_global {
+            return;
+        }
+        if !substitution.matched {
+            let pattern = self
+                .last_substitute
+                .as_ref()
+                .map(|substitute| substitute.pattern.clone())
+                .unwrap_or_default();
+            self.show(Message::error(format!("Pattern not found: {pattern}")));
+            return;
+        }
+        if let Some(line) = substitution.last_changed() {
+            let contents = self.get_current_buffer_contents();
+            let line = line.min(contents.len_lines() - 1);
+            let indent = contents
+                .line(line)
+                .map(|text| {
+                    text.chars()
+                        .take_while(|char| *char == ' ' || *char == '\t')
+                        .count()
+                })
+                .unwrap_or_default();
+            self.set_cursor_index(contents.line_to_char(line) + indent);
+        }
+        if substitution.count > 2 {
+            let plural = |count: usize| if count == 1 { "" } else { "s" };
+            self.show(Message::info(format!(
+                "{} substitution{} on {} line{}",
+                substitution.count,
+                plural(substitution.count),
+                substitution.changed_lines,
+                plural(substitution.changed_lines)
+            )));
+        }
+    }
+
+    /// Shows `message` and keeps it for `:messages`. Listings that are only
+    /// output, like `:ls`, set `message` directly instead.
+    pub fn show(&mut self, message: Message) {
+        if self.messages.len() == MESSAGE_HISTORY {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message.clone());
+        self.message = Some(message);
+    }
+
+    /// Opens the messages shown so far in a scratch buffer, replacing the
+    /// one an earlier `:messages` opened.
+    pub fn open_messages(&mut self) {
+        const NAME: &str = "[Messages]";
+        let text: Vec<&str> = self
+            .messages
+            .iter()
+            .map(|message| message.text.as_str())
+            .collect();
+        let messages = JimFile::scratch(NAME, &text.join("\n"));
+        let existing = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.scratch && buffer.path.as_os_str() == NAME);
+        let index = match existing {
+            Some(index) => {
+                self.buffers[index] = messages;
+                index
+            }
+            None => {
+                self.buffers.push(messages);
+                self.buffers.len() - 1
+            }
+        };
+        self.switch_buffer(index);
+        self.restore_cursor(Cursor::default());
+        self.move_cursor_down(self.messages.len());
+    }
+
+    /// Shows the error of a command or mapping that failed.
+    pub fn report(&mut self, result: Result<(), String>) {
+        if let Err(error) = result {
+            self.show(Message::error(error));
+        }
+    }
+
+    /// Lists the registers like vim's `:registers`.
+    pub fn show_registers(&mut self) {
+        let mut listing = String::from("Type Name Content");
+        let mut registers = self.registers.list();
+        registers.push(('%', self.read_register('%').unwrap()));
+        for (name, register) in registers {
+            let kind = match register.kind {
+                SelectionKind::Characterwise => 'c',
+                SelectionKind::Linewise => 'l',
+                SelectionKind::Blockwise => 'b',
+            };
+            let text = register.text.replace('\n', "^J");
+            listing.push_str(&format!("\n  {kind}  \"{name}   {text}"));
+        }
+        self.message = Some(Message::info(listing));
+    }
+
     pub fn write_char_to_current_buffer(&mut self, char: char) {
+        self.registers.record_insert(char);
         self.cursor
             .write_char_to(char, &mut self.buffers[self.recent_buffers[0]]);
     }
 
     pub fn backspace_current_buffer(&mut self) {
+        self.registers.record_backspace();
         self.cursor
             .backspace(&mut self.buffers[self.recent_buffers[0]])
     }
 
+    pub fn undo(&mut self) {
+        let buffer = &mut self.buffers[self.recent_buffers[0]];
+        if let Some(index) = buffer.undo() {
+            self.cursor.set_index(index, buffer);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        let buffer = &mut self.buffers[self.recent_buffers[0]];
+        if let Some(index) = buffer.redo() {
+            self.cursor.set_index(index, buffer);
+        }
+    }
+
+    pub fn undo_earlier(&mut self) {
+        let buffer = &mut self.buffers[self.recent_buffers[0]];
+        if let Some(index) = buffer.earlier() {
+            self.cursor.set_index(index, buffer);
+        }
+    }
+
+    pub fn undo_later(&mut self) {
+        let buffer = &mut self.buffers[self.recent_buffers[0]];
+        if let Some(index) = buffer.later() {
+            self.cursor.set_index(index, buffer);
+        }
+    }
+
+    pub fn commit_undo_unit(&mut self) {
+        if self.in_global {
+            return;
+        }
+        self.get_mut_current_buffer().commit_undo_unit();
+    }
+
+    /// Scrolls the current window so the cursor is in view.
+    pub fn scroll_to_cursor(&mut self) {
+        let area = self.windows.text_area(self.windows.current());
+        let size = Vector2::new(area.width as usize, area.height as usize);
+        let position = self.cursor.get_position(self.get_current_buffer());
+        let contents = &self.buffers[self.recent_buffers[0]].contents;
+        self.windows
+            .current_window_mut()
+            .scroll_to(position, contents, size, &self.options);
+    }
+
+    /// Scrolls the current window `lines` down, or up if negative, taking
+    /// the cursor along like `<C-d>` and `<C-u>`.
+    pub fn scroll_lines(&mut self, lines: isize) {
+        let line_count = self.get_current_buffer_contents().len_lines();
+        let height = self.window_height();
+        let window = self.windows.current_window_mut();
+        let amount = lines.unsigned_abs();
+        if lines > 0 {
+            let last_top = line_count.saturating_sub(height).max(window.top_line);
+            window.top_line = (window.top_line + amount).min(last_top);
+            self.move_cursor_down(amount);
+        } else {
+            window.top_line = window.top_line.saturating_sub(amount);
+            self.move_cursor_up(amount);
+        }
+    }
+
+    /// Scrolls the current window `pages` forward, or back if negative,
+    /// keeping two lines of the old page in view like `<C-f>` and `<C-b>`.
+    pub fn scroll_pages(&mut self, pages: isize) {
+        let line_count = self.get_current_buffer_contents().len_lines();
+        let height = self.window_height();
+        let scrolloff = self.scrolloff();
+        let amount = height.saturating_sub(2).max(1) * pages.unsigned_abs();
+        let window = self.windows.current_window_mut();
+        let y = if pages > 0 {
+            window.top_line = (window.top_line + amount).min(line_count - 1);
+            self.cursor.xy_pos.y.max(window.top_line + scrolloff)
+        } else {
+            window.top_line = window.top_line.saturating_sub(amount);
+            let last_shown = window.top_line + height.saturating_sub(1 + scrolloff);
+            self.cursor.xy_pos.y.min(last_shown)
+        };
+        self.cursor.xy_pos.y = y.min(line_count - 1);
+        self.cursor
+            .update_index(&self.buffers[self.recent_buffers[0]]);
+    }
+
+    /// Scrolls the current window to put the cursor line at `line`.
+    pub fn scroll_cursor_to(&mut self, line: ScreenLine) {
+        let height = self.window_height();
+        let scrolloff = self.scrolloff();
+        let y = self.cursor.xy_pos.y;
+        self.windows.current_window_mut().top_line = match line {
+            ScreenLine::Top => y.saturating_sub(scrolloff),
+            ScreenLine::Middle => y.saturating_sub(height.saturating_sub(1) / 2),
+            ScreenLine::Bottom => (y + scrolloff + 1).saturating_sub(height),
+        };
+    }
+
+    fn window_height(&self) -> usize {
+        self.windows.text_area(self.windows.current()).height as usize
+    }
+
+    /// `scrolloff`, limited to what fits in the current window.
+    fn scrolloff(&self) -> usize {
+        self.options
+            .scrolloff
+            .min(self.window_height().saturating_sub(1) / 2)
+    }
+
+    /// Opens the file or directory named on the cursor line of an explorer
+    /// buffer.
+    pub fn open_explorer_entry(&mut self) {
+        let buffer = self.get_current_buffer();
+        let name = Explorer::name_on(&self.current_line().to_string()).to_string();
+        let path = match name.as_str() {
+            "../" => buffer.path.parent().unwrap_or(&buffer.path).to_path_buf(),
+            name => buffer.path.join(name),
+        };
+        if let Err(error) = self.open_file(&path) {
+            self.show(Message::error(format!("\"{}\" {error}", path.display())));
+        }
+    }
+
+    /// Works out what the edits to the current explorer listing change on
+    /// disk and asks before doing it.
+    pub fn confirm_explorer_changes(&mut self) {
+        let JimFile {
+            path,
+            contents,
+            explorer: Some(explorer),
+            ..
+        } = self.get_mut_current_buffer()
+        else {
+            return;
+        };
+        match explorer.changes(path, contents) {
+            Err(error) => self.show(Message::error(error)),
+            Ok(changes) if changes.is_empty() => {
+                self.show(Message::info("Nothing to change"));
+            }
+            Ok(changes) => {
+                let mut question: Vec<String> = changes.iter().map(ToString::to_string).collect();
+                question.push(format!("Apply {} changes? (y/n)", changes.len()));
+                explorer.pending = changes;
+                self.message = Some(Message::info(question.join("\n")));
+                self.prompt = Some(Self::apply_explorer_changes);
+            }
+        }
+    }
+
+    fn apply_explorer_changes(&mut self, answer: char) {
+        let buffer = self.get_mut_current_buffer();
+        let Some(explorer) = &mut buffer.explorer else {
+            return;
+        };
+        if answer != 'y' {
+            explorer.pending.clear();
+            self.show(Message::info("Nothing changed"));
+            return;
+        }
+
+        // Whatever part of the changes got through, the listing should show
+        // what's on disk now.
+        let applied = explorer.apply();
+        let reloaded = buffer.reload_explorer();
+        self.restore_cursor(self.cursor);
+        self.show(match reloaded.and(applied) {
+            Ok(count) => Message::info(format!("Applied {count} changes")),
+            Err(error) => Message::error(format!("Could not apply the changes: {error}")),
+        });
+    }
+
+    /// Splits the current window, both halves showing the current buffer.
+    pub fn split_window(&mut self, split: Split) {
+        self.store_window();
+        self.windows.split(split);
+    }
+
+    /// Closes the current window, `false` if it's the last one.
+    pub fn close_window(&mut self) -> bool {
+        self.store_window();
+        if !self.windows.close() {
+            return false;
+        }
+        self.load_window();
+        true
+    }
+
+    pub fn focus_window(&mut self, id: usize) {
+        if id == self.windows.current() {
+            return;
+        }
+        self.store_window();
+        self.windows.focus(id);
+        self.load_window();
+    }
+
+    /// Focuses the `count`th window on `side`, as far as there are windows.
+    pub fn focus_neighbour(&mut self, side: Side, count: usize) {
+        for _ in 0..count {
+            match self.windows.neighbour(side) {
+                Some(id) => self.focus_window(id),
+                None => break,
+            }
+        }
+    }
+
+    /// Shows buffer `index` in the current window, putting the cursor back
+    /// where it was when the buffer was last left.
+    pub fn switch_buffer(&mut self, index: usize) {
+        if let Some(&current) = self.recent_buffers.front() {
+            if current == index {
+                return;
+            }
+            self.buffers[current].cursor = self.cursor;
+        }
+        self.make_recent(index);
+        self.restore_cursor(self.buffers[index].cursor);
+    }
+
+    /// The buffer shown before the current one, for `<C-^>`.
+    pub fn alternate_buffer(&self) -> Option<usize> {
+        self.recent_buffers.get(1).copied()
+    }
+
+    /// Finds a buffer by its number or by part of its name, which has to
+    /// match only one buffer.
+    pub fn find_buffer(&self, name: &str) -> Result<usize, String> {
+        if let Ok(number) = name.parse::<usize>() {
+            return match number {
+                1.. if number <= self.buffers.len() => Ok(number - 1),
+                _ => Err(format!("Buffer {number} does not exist")),
+            };
+        }
+        let mut matches = self
+            .buffers
+            .iter()
+            .enumerate()
+            .filter(|(_, buffer)| buffer.name().contains(name));
+        match (matches.next(), matches.next()) {
+            (Some((index, _)), None) => Ok(index),
+            (Some(_), Some(_)) => Err(format!("More than one match for {name}")),
+            (None, _) => Err(format!("No matching buffer for {name}")),
+        }
+    }
+
+    /// Removes buffer `index`, windows showing it switch to another buffer.
+    /// The last buffer can't be deleted.
+    pub fn delete_buffer(&mut self, index: usize) -> Result<(), String> {
+        if self.buffers.len() == 1 {
+            return Err("Cannot delete the last buffer".to_string());
+        }
+        let replacement = self
+            .recent_buffers
+            .iter()
+            .copied()
+            .find(|&buffer| buffer != index)
+            .unwrap_or(if index == 0 { 1 } else { 0 });
+
+        if self.recent_buffers[0] == index {
+            self.switch_buffer(replacement);
+        }
+        let replacement_cursor = self.buffers[replacement].cursor;
+        for window in self.windows.iter_mut() {
+            if window.buffer == index {
+                window.buffer = replacement;
+                window.cursor = replacement_cursor;
+            }
+            if window.buffer > index {
+                window.buffer -= 1;
+            }
+        }
+
+        self.buffers.remove(index);
+        self.recent_buffers.retain(|&buffer| buffer != index);
+        for buffer in self.recent_buffers.iter_mut() {
+            if *buffer > index {
+                *buffer -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Lists the buffers like vim's `:ls`.
+    pub fn list_buffers(&mut self) {
+        self.store_window();
+        let current = self.recent_buffers[0];
+        let alternate = self.alternate_buffer();
+        let shown: Vec<usize> = self.windows.iter().map(|window| window.buffer).collect();
+
+        let mut listing = vec![];
+        for (index, buffer) in self.buffers.iter().enumerate() {
+            let marker = if index == current {
+                '%'
+            } else if Some(index) == alternate {
+                '#'
+            } else {
+                ' '
+            };
+            let state = if index == current || shown.contains(&index) {
+                'a'
+            } else {
+                'h'
+            };
+            let line = if index == current {
+                self.cursor.xy_pos.y
+            } else {
+                buffer.cursor.xy_pos.y
+            };
+            listing.push(format!(
+                "{:>3} {marker}{state} {}  \"{}\"  line {}",
+                index + 1,
+                if buffer.modified { '+' } else { ' ' },
+                buffer.name(),
+                line + 1
+            ));
+        }
+        self.message = Some(Message::info(listing.join("\n")));
+    }
+
+    /// The first buffer with changes that haven't been written.
+    pub fn modified_buffer(&self) -> Option<usize> {
+        self.buffers
+            .iter()
+            .position(|buffer| buffer.modified && !buffer.scratch)
+    }
+
+    fn make_recent(&mut self, index: usize) {
+        if let Some(position) = self
+            .recent_buffers
+            .iter()
+            .position(|&buffer| buffer == index)
+        {
+            self.recent_buffers.remove(position);
+        }
+        self.recent_buffers.push_front(index);
+    }
+
+    /// Puts the cursor at `cursor` in the current buffer, which may have
+    /// changed since `cursor` was saved.
+    fn restore_cursor(&mut self, cursor: Cursor) {
+        let buffer = &self.buffers[self.recent_buffers[0]];
+        self.cursor = cursor;
+        self.cursor.xy_pos.y = self.cursor.xy_pos.y.min(buffer.contents.len_lines() - 1);
+        self.cursor.update_index(buffer);
+    }
+
+    fn store_window(&mut self) {
+        let (buffer, cursor) = (self.recent_buffers[0], self.cursor);
+        let window = self.windows.current_window_mut();
+        window.buffer = buffer;
+        window.cursor = cursor;
+    }
+
+    /// Makes the focused window's buffer and cursor current.
+    fn load_window(&mut self) {
+        let window = *self.windows.current_window();
+        self.make_recent(window.buffer);
+        self.restore_cursor(window.cursor);
+    }
+
+    /// Opens the files from the command line and puts the cursor where
+    /// they say, the first file ends up current. Without files the working
+    /// directory is opened.
+    pub fn open_args(&mut self, args: &Args) -> io::Result<()> {
+        let directory = std::env::current_dir()?;
+        if args.files.is_empty() {
+            return self.open_file(&directory);
+        }
+
+        let mut opened = vec![];
+        for file in &args.files {
+            self.open_file(&directory.join(&file.path))?;
+            self.get_mut_current_buffer().readonly = args.readonly;
+            if let Some(line) = file.line {
+                self.go_to_position(line, file.column.unwrap_or(1));
+            }
+            if opened.is_empty() {
+                match &args.start {
+                    Some(Start::Line(Some(line))) => self.go_to_position(*line, 1),
+                    Some(Start::Line(None)) => self.go_to_position(usize::MAX, 1),
+                    Some(Start::Search(pattern)) => self.go_to_first_match(pattern),
+                    None => {}
+                }
+            }
+            opened.push(self.recent_buffers[0]);
+        }
+
+        if args.diff {
+            // Splitting puts the new window on the left, so the windows are
+            // filled from the right.
+            self.switch_buffer(opened[opened.len() - 1]);
+            for &index in opened.iter().rev().skip(1) {
+                self.split_window(Split::Vertical);
+                self.switch_buffer(index);
+            }
+            for window in self.windows.iter_mut() {
+                window.diff = true;
+            }
+        } else {
+            self.switch_buffer(opened[0]);
+        }
+        Ok(())
+    }
+
+    /// Puts the cursor on `line` and `column`, both counted from 1 and
+    /// kept inside the buffer.
+    pub fn go_to_position(&mut self, line: usize, column: usize) {
+        let contents = self.get_current_buffer_contents();
+        let line = line.clamp(1, contents.len_lines()) - 1;
+        let length = contents.line_len(line).unwrap_or_default();
+        let column = (column.max(1) - 1).min(length.saturating_sub(1));
+        self.set_cursor_index(contents.line_to_char(line) + column);
+    }
+
+    /// Goes to the first match of `pattern` in the buffer, for `+/pattern`.
+    /// It becomes the last search, so `n` goes on to the next one.
+    fn go_to_first_match(&mut self, pattern: &str) {
+        self.search.pattern = Some(pattern.to_string());
+        self.search.backward = false;
+        self.search.smartcase = true;
+        self.search.history.remember(pattern);
+        let found = search::regex(pattern, &self.options, true).and_then(|regex| {
+            let contents = self.get_current_buffer_contents();
+            search::Haystack::new(contents)
+                .first(contents, &regex)
+                .ok_or(format!("Pattern not found: {pattern}"))
+        });
+        match found {
+            Ok(found) => self.set_cursor_index(found.range.start),
+            Err(error) => self.show(Message::error(error)),
+        }
+    }
+
+    /// The buffer window `id` shows.
+    pub fn window_buffer(&self, id: usize) -> usize {
+        match self.windows.get(id) {
+            Some(_) if id == self.windows.current() => self.recent_buffers[0],
+            Some(window) => window.buffer,
+            None => self.recent_buffers[0],
+        }
+    }
+
+    /// Switches to the buffer for `path`, reading the file if it isn't
+    /// open yet.
     pub fn open_file(&mut self, path: &Path) -> io::Result<()> {
-        self.buffers.push(JimFile::new(&path)?);
-        self.recent_buffers.push_front(self.buffers.len() - 1);
+        let path = file::normalize_path(path);
+        let index = match self.buffers.iter().position(|buffer| buffer.path == path) {
+            Some(index) => index,
+            None => {
+                self.buffers.push(JimFile::new(&path)?);
+                self.buffers.len() - 1
+            }
+        };
+        self.switch_buffer(index);
+        if self.get_current_buffer().is_new {
+            self.show(Message::info(format!(
+                "\"{}\" [New]",
+                self.get_current_buffer().name()
+            )));
+        }
         Ok(())
     }
 
+    /// Reads the current buffer from its file again.
+    pub fn reload_buffer(&mut self) -> io::Result<()> {
+        let current = self.recent_buffers[0];
+        self.buffers[current].reload(&self.options)?;
+        self.restore_cursor(self.cursor);
+        Ok(())
+    }
+
+    /// Looks for buffers whose file something else changed, reloading the
+    /// unmodified ones when `autoread` is set and warning about the rest.
+    pub fn check_files(&mut self) {
+        let mut changed = vec![];
+        for (index, buffer) in self.buffers.iter_mut().enumerate() {
+            if buffer.changed_on_disk() {
+                changed.push(index);
+            }
+        }
+        for index in changed {
+            let buffer = &mut self.buffers[index];
+            let name = buffer.name();
+            if !self.options.autoread || buffer.modified {
+                self.show(Message::warning(format!(
+                    "\"{name}\" has changed since reading it, :e! reloads it"
+                )));
+                continue;
+            }
+            let reloaded = buffer.reload(&self.options);
+            if index == self.recent_buffers[0] {
+                self.restore_cursor(self.cursor);
+            }
+            self.show(match reloaded {
+                Ok(()) => Message::info(format!("\"{name}\" reloaded")),
+                Err(error) => Message::error(format!("\"{name}\" {error}")),
+            });
+        }
+    }
+
+    /// Watches the files of buffers opened since the last call.
+    fn watch_files(&mut self) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        let files = self
+            .buffers
+            .iter()
+            .filter(|buffer| buffer.explorer.is_none() && !buffer.scratch);
+        for buffer in files {
+            watcher.watch(&buffer.path);
+        }
+    }
+
     pub fn force_move_cursor_right(&mut self, amount: usize) {
         for _ in 0..amount {
             self.cursor
@@ -119,11 +1180,13 @@ impl JimProperties {
     }
 
     pub fn move_cursor_full_right(&mut self) {
-        self.cursor.move_full_right(&self.buffers[self.recent_buffers[0]]);
+        self.cursor
+            .move_full_right(&self.buffers[self.recent_buffers[0]]);
     }
 
     pub fn move_cursor_full_left(&mut self) {
-        self.cursor.move_full_left();
+        self.cursor
+            .move_full_left(&self.buffers[self.recent_buffers[0]]);
     }
 }
 
@@ -133,7 +1196,10 @@ impl Jim {
     }
 
     pub fn init(mut self) -> io::Result<Self> {
-        self.properties.open_file(self.get_workspace()?.as_path())?;
+        let args = Args::parse(std::env::args().skip(1))
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        self.properties.open_args(&args)?;
+        self.properties.watcher = FileWatcher::new().ok();
 
         Ok(self)
     }
@@ -145,66 +1211,524 @@ impl Jim {
             }
 
             terminal.draw(|f| self.render(f))?;
+            self.wait()?;
+        }
+        Ok(())
+    }
 
-            if let Event::Key(key) = event::read()? {
-                match self.properties.mode {
-                    Mode::Normal => self.normal(key),
-                    Mode::Insert => self.insert(key),
-                    Mode::Command => self.command(key),
+    /// Waits for a key or for a watched file to change, and handles it.
+    fn wait(&mut self) -> io::Result<()> {
+        self.properties.watch_files();
+        loop {
+            if event::poll(WATCH_INTERVAL)? {
+                match event::read()? {
+                    Event::Key(key) => self.handle_key(key),
+                    Event::FocusGained => self.properties.check_files(),
+                    _ => {}
                 }
+                return Ok(());
+            }
+            let changed = match &mut self.properties.watcher {
+                Some(watcher) => watcher.changed(),
+                None => false,
+            };
+            if changed {
+                self.properties.check_files();
+                return Ok(());
             }
         }
-        Ok(())
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        self.properties.message = None;
+        if let Some(on_answer) = self.properties.prompt.take() {
+            // Keys that aren't characters answer like `q`, giving up.
+            let answer = match key.code {
+                KeyCode::Char(char) => char,
+                _ => 'q',
+            };
+            on_answer(&mut self.properties, answer);
+            return;
+        }
+        match self.properties.mode {
+            Mode::Normal => self.normal(key),
+            Mode::Insert => self.insert(key),
+            Mode::Command => self.command(key),
+            Mode::Search => self.search(key),
+            Mode::Visual | Mode::VisualLine | Mode::VisualBlock => self.visual(key),
+            Mode::OperatorPending => self.operator_pending(key),
+        }
     }
 
     fn render<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let message_height = match &self.properties.message {
+            Some(message) if !self.properties.mode.is_command_line() => {
+                message.text.lines().count()
+            }
+            _ => 1,
+        };
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .margin(0)
-            .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+            .constraints(
+                [
+                    Constraint::Min(0),
+                    Constraint::Length(message_height as u16),
+                ]
+                .as_ref(),
+            )
             .split(f.size());
 
-        let text = Text::raw(
-            self.properties.get_current_buffer_contents().clone()
-                + "\n"
-                + &self.properties.cursor.index.to_string()
-                + "\n"
-                + &self.properties.cursor.xy_pos.to_string(),
-        );
-        let file = Paragraph::new(text);
-        f.render_widget(file, chunks[0]);
+        self.properties.windows.area = chunks[0];
+        self.properties.scroll_to_cursor();
+        for (id, area) in self.properties.windows.areas(chunks[0]) {
+            self.render_window(f, id, area);
+            if area.right() < chunks[0].right() {
+                let separator = Paragraph::new("│\n".repeat(area.height as usize));
+                f.render_widget(separator, Rect::new(area.right(), area.y, 1, area.height));
+            }
+        }
 
-        let command_paragraph = Paragraph::new(self.command.clone());
+        let command_paragraph = match &self.properties.message {
+            Some(message) if !self.properties.mode.is_command_line() => {
+                Paragraph::new(message.text.clone()).style(message.style())
+            }
+            _ if self.properties.mode.is_command_line() => {
+                Paragraph::new(format!("{}{}", self.prompt_char(), self.command.text()))
+            }
+            _ => Paragraph::new(""),
+        };
         f.render_widget(command_paragraph, chunks[1]);
+        if self.properties.mode.is_command_line() {
+            let column = 1 + self.command.cursor_column() as u16;
+            f.set_cursor(chunks[1].x + column, chunks[1].y);
+        }
+    }
 
-        let cursor_pos = self.get_cursor_position();
-        f.set_cursor(cursor_pos.x as u16, cursor_pos.y as u16)
+    /// What the line at the bottom starts with, `:` for commands and `/`
+    /// or `?` for searches.
+    fn prompt_char(&self) -> char {
+        match &self.properties.search_prompt {
+            _ if self.properties.mode == Mode::Command => ':',
+            Some(prompt) if prompt.backward => '?',
+            _ => '/',
+        }
+    }
+
+    /// Draws a window with its status line below it. The current window
+    /// also gets the cursor.
+    fn render_window<B: Backend>(&mut self, f: &mut Frame<B>, id: usize, area: Rect) {
+        let is_current = id == self.properties.windows.current();
+        let window = *self.properties.windows.get(id).unwrap();
+        let changed = self.diff_lines(id).unwrap_or_default();
+        let properties = &self.properties;
+        let (buffer, selection) = if is_current {
+            (properties.get_current_buffer(), properties.selection())
+        } else {
+            (&properties.buffers[window.buffer], None)
+        };
+        let text_area = window::text_area(area);
+        let lines = window.top_line..window.top_line + text_area.height as usize;
+        let text = buffer_text(buffer, selection, lines, &changed);
+        f.render_widget(
+            Paragraph::new(text).scroll((0, window.left_column as u16)),
+            text_area,
+        );
+
+        let mut status_style = Style::default().add_modifier(Modifier::REVERSED);
+        if is_current {
+            status_style = status_style.add_modifier(Modifier::BOLD);
+        }
+        let mut status = buffer.name();
+        if buffer.is_new {
+            status.push_str(" [New]");
+        }
+        if buffer.modified {
+            status.push_str(" [+]");
+        }
+        if buffer.readonly {
+            status.push_str(" [RO]");
+        }
+        let status = Paragraph::new(status).style(status_style);
+        f.render_widget(status, Rect::new(area.x, area.bottom() - 1, area.width, 1));
+
+        if is_current {
+            let position = self.get_cursor_position();
+            f.set_cursor(
+                area.x + (position.x - window.left_column) as u16,
+                area.y + (position.y - window.top_line) as u16,
+            );
+        }
+    }
+
+    /// For a diff window, which of its lines differ from the next diff
+    /// window's. Only worked out again once either buffer changes.
+    fn diff_lines(&mut self, id: usize) -> Option<Vec<bool>> {
+        let properties = &self.properties;
+        if !properties.windows.get(id)?.diff {
+            return None;
+        }
+        let areas = properties.windows.areas(properties.windows.area);
+        let position = areas.iter().position(|&(window, _)| window == id)?;
+        let other = areas
+            .iter()
+            .cycle()
+            .skip(position + 1)
+            .take(areas.len() - 1)
+            .map(|&(window, _)| window)
+            .find(|&window| {
+                properties
+                    .windows
+                    .get(window)
+                    .is_some_and(|window| window.diff)
+            })?;
+
+        let buffers = (
+            properties.window_buffer(id),
+            properties.window_buffer(other),
+        );
+        let contents = |buffer: usize| &properties.buffers[buffer].contents;
+        let revisions = (
+            contents(buffers.0).revision(),
+            contents(buffers.1).revision(),
+        );
+        if let Some(diff) = self.diffs.get(&buffers) {
+            if diff.revisions == revisions {
+                return Some(diff.changed.clone());
+            }
+        }
+
+        let lines = |buffer: usize| -> Vec<String> {
+            let contents = contents(buffer);
+            (0..contents.len_lines())
+                .map(|line| contents.line(line).unwrap().to_string())
+                .collect()
+        };
+        let changed = diff::changed_lines(&lines(buffers.0), &lines(buffers.1)).0;
+        self.diffs.insert(
+            buffers,
+            Diff {
+                revisions,
+                changed: changed.clone(),
+            },
+        );
+        Some(changed)
     }
 
     fn insert(&mut self, key: KeyEvent) {
         match key.code {
-            KeyCode::Char('e') => self.to_normal_mode(),
+            KeyCode::Esc => self.enter_normal_mode(),
             KeyCode::Char(char) => self.properties.write_char_to_current_buffer(char),
             KeyCode::Enter => self.properties.write_char_to_current_buffer('\n'),
+            KeyCode::Tab => self.properties.write_char_to_current_buffer('\t'),
             KeyCode::Backspace => self.properties.backspace_current_buffer(),
-            _ => todo!(),
+            _ => {}
         }
     }
 
     fn command(&mut self, key: KeyEvent) {
+        if !matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+            self.completion = None;
+        }
         match key.code {
-            KeyCode::Char('e') => self.to_normal_mode(),
-            KeyCode::Char(char) => self.write_char_to_command(char),
+            _ if self.cancels_command_line(key) => {
+                self.end_command_line();
+                self.properties.mode = Mode::Normal;
+            }
             KeyCode::Enter => self.run_commands(),
-            KeyCode::Backspace => self.backspace_command(),
-            _ => todo!(),
+            KeyCode::Up | KeyCode::Down => self.browse_history(key.code == KeyCode::Up),
+            KeyCode::Tab | KeyCode::BackTab => self.complete(key.code == KeyCode::BackTab),
+            _ => {
+                self.edit_command_line(key);
+            }
+        }
+    }
+
+    fn search(&mut self, key: KeyEvent) {
+        match key.code {
+            _ if self.cancels_command_line(key) => {
+                self.end_command_line();
+                self.properties.cancel_search();
+                return;
+            }
+            KeyCode::Enter => {
+                let pattern = self.end_command_line();
+                let result = self.properties.finish_search(&pattern);
+                self.properties.report(result);
+                if self.properties.mode == Mode::Normal {
+                    self.properties.commit_undo_unit();
+                }
+                return;
+            }
+            KeyCode::Up | KeyCode::Down => self.browse_history(key.code == KeyCode::Up),
+            _ if self.edit_command_line(key) => {}
+            _ => return,
+        }
+        self.properties.preview_search(self.command.text());
+    }
+
+    /// `<Esc>` and `<C-c>` give up on the line, and so does `<BS>` once
+    /// there's nothing left to delete.
+    fn cancels_command_line(&self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Esc => true,
+            KeyCode::Char('c') => key.modifiers.contains(KeyModifiers::CONTROL),
+            KeyCode::Backspace => self.command.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Empties the prompt, returning what was typed.
+    fn end_command_line(&mut self) -> String {
+        self.browse = None;
+        self.completion = None;
+        self.command.take()
+    }
+
+    /// Moves around and edits the line at a prompt, returning whether `key`
+    /// was one that does.
+    fn edit_command_line(&mut self, key: KeyEvent) -> bool {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        let by_word = control || key.modifiers.contains(KeyModifiers::SHIFT);
+        let line = &mut self.command;
+        match key.code {
+            KeyCode::Char('w') if control => line.delete_word(),
+            KeyCode::Char('u') if control => line.delete_to_start(),
+            KeyCode::Char('b') if control => line.home(),
+            KeyCode::Char('e') if control => line.end(),
+            KeyCode::Char('h') if control => line.backspace(),
+            KeyCode::Char(char) if !control => line.insert(char),
+            KeyCode::Left if by_word => line.word_left(),
+            KeyCode::Right if by_word => line.word_right(),
+            KeyCode::Left => line.left(),
+            KeyCode::Right => line.right(),
+            KeyCode::Home => line.home(),
+            KeyCode::End => line.end(),
+            KeyCode::Backspace => line.backspace(),
+            KeyCode::Delete => line.delete(),
+            _ => return false,
+        }
+        // What's typed now is the prefix the next `<Up>` looks for.
+        self.browse = None;
+        true
+    }
+
+    /// Puts an older line from the history on the prompt, or a newer one
+    /// and finally what was typed. Only lines that start with what was
+    /// typed come up.
+    fn browse_history(&mut self, older: bool) {
+        let history = match self.properties.mode {
+            Mode::Search => &self.properties.search.history,
+            _ => &self.properties.command_history,
+        };
+        let browse = self.browse.get_or_insert_with(|| Browse {
+            prefix: self.command.text().to_string(),
+            index: None,
+        });
+        let next = match (older, browse.index) {
+            (true, from) => history.older(&browse.prefix, from),
+            (false, Some(from)) => history.newer(&browse.prefix, from),
+            (false, None) => return,
+        };
+        match next {
+            Some((index, line)) => {
+                browse.index = Some(index);
+                self.command.set(line);
+            }
+            None if !older => {
+                browse.index = None;
+                self.command.set(&browse.prefix);
+            }
+            None => {}
+        }
+    }
+
+    /// Completes the word before the cursor, or goes on to the next
+    /// completion of it, the one before with `backward`.
+    fn complete(&mut self, backward: bool) {
+        let completion = match self.completion.take() {
+            Some(mut completion) => {
+                let count = completion.candidates.len();
+                completion.index = match backward {
+                    false => (completion.index + 1) % count,
+                    true => (completion.index + count - 1) % count,
+                };
+                completion
+            }
+            None => {
+                let (start, candidates) = self.completions();
+                if candidates.is_empty() {
+                    return;
+                }
+                let index = if backward { candidates.len() - 1 } else { 0 };
+                Completion {
+                    start,
+                    candidates,
+                    index,
+                }
+            }
+        };
+        let candidate = &completion.candidates[completion.index];
+        self.command
+            .replace_before_cursor(completion.start, candidate);
+        self.completion = Some(completion);
+    }
+
+    /// Where the word before the cursor starts and what it can complete
+    /// to: a command name, or a file or buffer name for the commands that
+    /// take one.
+    fn completions(&self) -> (usize, Vec<String>) {
+        let before = self.command.before_cursor();
+        // Ranges have no letters in them, the name starts at the first.
+        let name_start = before
+            .find(|char: char| char.is_ascii_alphabetic())
+            .unwrap_or(before.len());
+        let name_length = before[name_start..]
+            .find(|char: char| !char.is_ascii_alphabetic())
+            .unwrap_or(before.len() - name_start);
+        let name = &before[name_start..name_start + name_length];
+        let after = &before[name_start + name_length..];
+
+        if after.is_empty() {
+            if before[..name_start].contains(char::is_whitespace) {
+                return (name_start, vec![]);
+            }
+            let mut names: Vec<String> = self
+                .cmaps
+                .iter()
+                .map(|command| command.name().to_string())
+                .filter(|command| command.starts_with(name))
+                .collect();
+            names.sort();
+            names.dedup();
+            return (name_start, names);
         }
+
+        if !after
+            .trim_start_matches('!')
+            .starts_with(char::is_whitespace)
+        {
+            return (before.len(), vec![]);
+        }
+        let word_start = before
+            .rfind(char::is_whitespace)
+            .map_or(0, |index| index + 1);
+        let word = &before[word_start..];
+        let kind = find_command(&self.cmaps, name).map_or(ArgumentKind::None, |command| {
+            ArgumentKind::of(command.name())
+        });
+        let candidates = match kind {
+            ArgumentKind::File => command_line::complete_path(word),
+            ArgumentKind::Buffer => self
+                .properties
+                .buffers
+                .iter()
+                .map(JimFile::name)
+                .filter(|buffer| buffer.contains(word))
+                .collect(),
+            ArgumentKind::None => vec![],
+        };
+        (word_start, candidates)
     }
 
     fn normal(&mut self, key: KeyEvent) {
-        self.properties.buttons_pressed.push(key.code);
-        for map in &mut self.nmaps {
-            map.try_use(&mut self.properties)
+        if self.push_prefix(key) {
+            return;
+        }
+        self.properties.buttons_pressed.push(normalize_key(key));
+        let lookup = lookup_mapping(&self.nmaps, &self.properties.buttons_pressed);
+        self.run_mapping(lookup);
+        if self.properties.mode == Mode::Normal {
+            self.properties.commit_undo_unit();
+        }
+    }
+
+    fn operator_pending(&mut self, key: KeyEvent) {
+        if self.push_prefix(key) {
+            return;
+        }
+        self.properties.buttons_pressed.push(normalize_key(key));
+        let motions = self.nmaps.iter().filter(|map| map.motion().is_some());
+        let lookup = lookup_mapping(
+            self.omaps.iter().chain(motions),
+            &self.properties.buttons_pressed,
+        );
+
+        match self.run_mapping(lookup) {
+            Some(Some(kind)) => self.properties.apply_operator_to_motion(kind),
+            Some(None) => {}
+            None if !self.properties.buttons_pressed.is_empty() => return,
+            None => {}
+        }
+        // Text objects apply the operator themselves, anything else that
+        // isn't a motion cancels it.
+        if self.properties.mode == Mode::OperatorPending {
+            self.properties.cancel_operator();
+        }
+        if self.properties.mode == Mode::Normal {
+            self.properties.commit_undo_unit();
+        }
+    }
+
+    /// Takes `key` as part of a count or a `"x` register prefix if it comes
+    /// before any mapping. A leading 0 isn't a count, it's the motion.
+    fn push_prefix(&mut self, key: KeyEvent) -> bool {
+        if !self.properties.buttons_pressed.is_empty() {
+            return false;
+        }
+        let char = match key.code {
+            KeyCode::Char(char) if !key.modifiers.contains(KeyModifiers::CONTROL) => Some(char),
+            _ => None,
+        };
+
+        if self.awaiting_register {
+            self.awaiting_register = false;
+            self.register = char.filter(|&name| Registers::is_valid_name(name));
+            return true;
+        }
+        if char == Some('"') {
+            self.awaiting_register = true;
+            return true;
+        }
+
+        let digit = char.and_then(|char| char.to_digit(10));
+        match (digit, self.count) {
+            (Some(0), None) | (None, _) => false,
+            (Some(digit), count) => {
+                let count = count.unwrap_or(0).saturating_mul(10);
+                self.count = Some(count.saturating_add(digit as usize));
+                true
+            }
+        }
+    }
+
+    /// Runs the mapping found by `lookup_mapping`, returning its motion kind
+    /// if it ran. Keys that can't lead to any mapping are dropped.
+    fn run_mapping(&mut self, lookup: Lookup) -> Option<Option<MotionKind>> {
+        match lookup {
+            Lookup::Found(on_pressed, motion) => {
+                let mut count = self.count.take();
+                let mut register = self.register.take();
+                // A count before the operator multiplies the motion's, `2d3w`
+                // deletes 6 words.
+                if self.properties.mode == Mode::OperatorPending {
+                    let operator_context = self.properties.operator_context;
+                    if let Some(operator_count) = operator_context.count {
+                        count = Some(operator_count * count.unwrap_or(1));
+                    }
+                    register = register.or(operator_context.register);
+                }
+                on_pressed(&mut self.properties, MapContext { count, register });
+                self.properties.buttons_pressed.clear();
+                Some(motion)
+            }
+            Lookup::Pending => None,
+            Lookup::None => {
+                self.count = None;
+                self.register = None;
+                self.properties.buttons_pressed.clear();
+                None
+            }
         }
     }
 
@@ -213,30 +1737,54 @@ impl Jim {
         self
     }
 
+    fn visual(&mut self, key: KeyEvent) {
+        if self.push_prefix(key) {
+            return;
+        }
+        self.properties.buttons_pressed.push(normalize_key(key));
+        let motions = self.nmaps.iter().filter(|map| map.motion().is_some());
+        let lookup = lookup_mapping(
+            self.vmaps.iter().chain(&self.omaps).chain(motions),
+            &self.properties.buttons_pressed,
+        );
+        self.run_mapping(lookup);
+        if self.properties.mode == Mode::Normal {
+            self.properties.commit_undo_unit();
+        }
+    }
+
     pub fn add_imaps(mut self, imaps: fn() -> Vec<Mapping>) -> Self {
         self.imaps.append(&mut (imaps)());
         self
     }
 
-    pub fn add_cmaps(mut self, cmaps: fn() -> Vec<Command>) -> Self {
-        self.cmaps.append(&mut (cmaps)());
+    pub fn add_vmaps(mut self, vmaps: fn() -> Vec<Mapping>) -> Self {
+        self.vmaps.append(&mut (vmaps)());
         self
     }
 
-    fn to_normal_mode(&mut self) {
-        self.properties.mode = Mode::Normal;
-        self.properties.move_cursor_left(1);
+    pub fn add_omaps(mut self, omaps: fn() -> Vec<Mapping>) -> Self {
+        self.omaps.append(&mut (omaps)());
+        self
     }
 
-    fn get_workspace(&self) -> io::Result<PathBuf> {
-        let args: Vec<String> = std::env::args().collect();
+    pub fn add_cmaps(mut self, cmaps: fn() -> Vec<Command>) -> Self {
+        self.cmaps.append(&mut (cmaps)());
+        self
+    }
 
-        if args.len() > 1 {
-            let path = &args[1];
-            Ok(std::env::current_dir()?.join(path))
-        } else {
-            std::env::current_dir()
+    fn enter_normal_mode(&mut self) {
+        self.properties.mode = Mode::Normal;
+        if let Some(insert) = self.properties.block_insert.take() {
+            let end = self.properties.cursor.index;
+            insert.finish(&mut self.properties, end);
         }
+        self.properties.move_cursor_left(1);
+        // A line break typed at the end leaves the cursor after the last line.
+        let index = self.properties.cursor.index;
+        self.properties.set_cursor_index(index);
+        self.properties.commit_undo_unit();
+        self.properties.registers.finish_insert();
     }
 
     fn get_cursor_position(&mut self) -> Vector2<usize> {
@@ -245,19 +1793,338 @@ impl Jim {
             .get_position(self.properties.get_current_buffer())
     }
 
-    fn write_char_to_command(&mut self, char: char) {
-        self.command.push(char);
+    fn run_commands(&mut self) {
+        let command = self.end_command_line();
+        self.properties.registers.last_command = command.clone();
+        self.properties.command_history.remember(&command);
+        self.properties.mode = Mode::Normal;
+        let result = ExCommand::parse(&command).and_then(|command| self.run_ex_command(&command));
+        self.properties.report(result);
+        self.properties.commit_undo_unit();
     }
 
-    fn run_commands(&mut self) {
-        for command in &mut self.cmaps {
-            command.try_use(&mut self.properties, &self.command)
+    /// Runs a parsed command, a range alone like `:10` goes to its line.
+    fn run_ex_command(&mut self, command: &ExCommand) -> Result<(), String> {
+        if command.name.is_empty() {
+            if let Some(range) = command.range {
+                let line_count = self.properties.get_current_buffer_contents().len_lines();
+                let line = range.last_line(self.properties.cursor.xy_pos.y, line_count)?;
+                self.properties.go_to_position(line + 1, 1);
+            }
+            return Ok(());
         }
-        self.command.clear();
-        self.properties.mode = Mode::Normal;
+        match find_command(&self.cmaps, &command.name)?.action(command)? {
+            CommandAction::Properties(run) => run(&mut self.properties, command),
+            CommandAction::Editor(run) => run(self, command),
+        }
+    }
+
+    /// `:g/pattern/command`, runs `command` on each line in the range,
+    /// all of them without one, that matches. `:v` and `:g!` run it on the
+    /// lines that don't. Lines are marked first so the command can add and
+    /// delete lines, and the whole of it undoes as one change.
+    pub fn global(&mut self, command: &ExCommand, invert: bool) -> Result<(), String> {
+        if self.properties.in_global {
+            return Err("Cannot do :global recursive".to_string());
+        }
+        let invert = invert || command.bang;
+        let (_, mut pattern, rest) = ex::parse_pattern(&command.arguments)?;
+        let properties = &mut self.properties;
+        if pattern.is_empty() {
+            pattern = properties
+                .search
+                .pattern
+                .clone()
+                .ok_or("No previous regular expression")?;
+        }
+        let regex = search::regex(&pattern, &properties.options, true)?;
+        properties.search.pattern = Some(pattern.clone());
+        properties.search.smartcase = true;
+
+        let contents = properties.get_current_buffer_contents();
+        let (first, last) = match command.range {
+            Some(range) => range.resolve(properties.cursor.xy_pos.y, contents.len_lines())?,
+            None => (0, contents.len_lines().saturating_sub(1)),
+        };
+        let lines: Vec<usize> = (first..=last)
+            .filter(|&line| {
+                let text = contents.line(line).map(|line| line.to_string());
+                regex.is_match(&text.unwrap_or_default()) != invert
+            })
+            .collect();
+        if lines.is_empty() {
+            return Err(match invert {
+                false => format!("Pattern not found: {pattern}"),
+                true => format!("Pattern found in every line: {pattern}"),
+            });
+        }
+
+        // Without a command the lines are listed, like vim's `:p`.
+        let to_run = rest.unwrap_or_default().trim();
+        if to_run.is_empty() {
+            let listing: Vec<String> = lines
+                .iter()
+                .filter_map(|&line| contents.line(line).map(|line| line.to_string()))
+                .collect();
+            properties.message = Some(Message::info(listing.join("\n")));
+            return Ok(());
+        }
+
+        let marks = lines.iter().rev().map(|&line| contents.line_to_char(line));
+        let marks = marks.collect();
+        properties.commit_undo_unit();
+        let buffer = properties.recent_buffers[0];
+        properties.buffers[buffer].marked_lines = marks;
+        properties.in_global = true;
+        let mut result = Ok(());
+        while self.properties.recent_buffers[0] == buffer && !self.properties.quitting {
+            let Some(mark) = self.properties.buffers[buffer].marked_lines.pop() else {
+                break;
+            };
+            self.properties.set_cursor_index(mark);
+            let ran = ExCommand::parse(to_run).and_then(|command| self.run_ex_command(&command));
+            // Keep going past errors, but report the first.
+            if result.is_ok() {
+                result = ran;
+            }
+        }
+        self.properties.buffers[buffer].marked_lines.clear();
+        self.properties.in_global = false;
+        self.properties.commit_undo_unit();
+        result
+    }
+
+    /// `:normal keys`, types `keys` in normal mode on each line of the
+    /// range, or where the cursor is without one. Whatever they leave
+    /// unfinished is given up like with `<Esc>`.
+    pub fn normal_command(&mut self, command: &ExCommand) -> Result<(), String> {
+        let lines = match command.range {
+            Some(_) => Some(self.properties.command_lines(command.range)?),
+            None => None,
+        };
+        let Some((first, last)) = lines else {
+            self.type_keys(&command.arguments);
+            return Ok(());
+        };
+        for line in first..=last {
+            let contents = self.properties.get_current_buffer_contents();
+            if line >= contents.len_lines() {
+                break;
+            }
+            self.properties
+                .set_cursor_index(contents.line_to_char(line));
+            self.type_keys(&command.arguments);
+        }
+        Ok(())
+    }
+
+    fn type_keys(&mut self, keys: &str) {
+        for char in keys.chars() {
+            self.handle_key(KeyEvent::from(KeyCode::Char(char)));
+        }
+        // Each `<Esc>` backs out of one thing, a search started in visual
+        // mode takes two.
+        for _ in 0..3 {
+            let properties = &self.properties;
+            let unfinished = properties.mode != Mode::Normal
+                || properties.prompt.is_some()
+                || !properties.buttons_pressed.is_empty()
+                || self.count.is_some()
+                || self.register.is_some()
+                || self.awaiting_register;
+            if !unfinished {
+                break;
+            }
+            self.handle_key(KeyEvent::from(KeyCode::Esc));
+        }
+    }
+}
+
+/// The command `name` abbreviates. Two commands claiming the same
+/// abbreviation is an error rather than a guess.
+fn find_command<'a>(commands: &'a [Command], name: &str) -> Result<&'a Command, String> {
+    let matching: Vec<&Command> = commands
+        .iter()
+        .filter(|command| command.matches(name))
+        .collect();
+    match matching.as_slice() {
+        [] => Err(format!("Not an editor command: {name}")),
+        [command] => Ok(command),
+        _ => Err(format!("Ambiguous command: {name}")),
+    }
+}
+
+enum Lookup {
+    Found(fn(&mut JimProperties, MapContext), Option<MotionKind>),
+    Pending,
+    None,
+}
+
+fn lookup_mapping<'a>(maps: impl IntoIterator<Item = &'a Mapping>, pressed: &[KeyEvent]) -> Lookup {
+    let mut pending = false;
+    for map in maps {
+        match map.matches(pressed) {
+            KeyMatch::Full => return Lookup::Found(map.on_pressed(), map.motion()),
+            KeyMatch::Partial => pending = true,
+            KeyMatch::None => {}
+        }
+    }
+    if pending {
+        Lookup::Pending
+    } else {
+        Lookup::None
+    }
+}
+
+/// `lines` of `buffer`, with `selection` and the lines marked in `changed`
+/// highlighted.
+fn buffer_text(
+    buffer: &JimFile,
+    selection: Option<Selection>,
+    lines: Range<usize>,
+    changed: &[bool],
+) -> Text<'static> {
+    let highlight = Style::default().add_modifier(Modifier::REVERSED);
+    let changed_style = Style::default().bg(Color::Blue);
+
+    let mut text = vec![];
+    for y in lines.start..lines.end.min(buffer.contents.len_lines()) {
+        let line: Vec<char> = buffer.contents.line(y).unwrap().chars().collect();
+        let style = match changed.get(y) {
+            Some(true) => changed_style,
+            _ => Style::default(),
+        };
+        let Some(columns) = selection.and_then(|selection| selection.columns_on_line(y, buffer))
+        else {
+            text.push(Spans::from(Span::styled(
+                line.into_iter().collect::<String>(),
+                style,
+            )));
+            continue;
+        };
+
+        let mut selected: String = line[columns.clone()].iter().collect();
+        if selected.is_empty() {
+            // Keep selected empty lines visible.
+            selected.push(' ');
+        }
+        text.push(Spans::from(vec![
+            Span::styled(line[..columns.start].iter().collect::<String>(), style),
+            Span::styled(selected, style.patch(highlight)),
+            Span::styled(line[columns.end..].iter().collect::<String>(), style),
+        ]));
+    }
+    Text::from(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin_maps;
+
+    fn jim(text: &str) -> Jim {
+        let mut jim = Jim::new()
+            .add_nmaps(builtin_maps::nmaps)
+            .add_imaps(builtin_maps::imaps)
+            .add_vmaps(builtin_maps::vmaps)
+            .add_omaps(builtin_maps::omaps)
+            .add_cmaps(builtin_maps::cmaps);
+        let mut file = JimFile::default();
+        file.contents = Buffer::from(text);
+        jim.properties.buffers.push(file);
+        jim.properties.recent_buffers.push_front(0);
+        jim
+    }
+
+    /// Presses `keys`, with `<CR>` for enter.
+    fn press(jim: &mut Jim, keys: &str) {
+        for (index, part) in keys.split("<CR>").enumerate() {
+            if index > 0 {
+                jim.handle_key(KeyEvent::from(KeyCode::Enter));
+            }
+            for char in part.chars() {
+                jim.handle_key(KeyEvent::from(KeyCode::Char(char)));
+            }
+        }
+    }
+
+    fn text(jim: &Jim) -> String {
+        jim.properties.get_current_buffer_contents().to_string()
+    }
+
+    #[test]
+    fn global_refuses_to_confirm_substitutions() {
+        let mut jim = jim("ax\nb\nax\nax\n");
+        press(&mut jim, ":g/a/s/x/y/c<CR>yyy");
+        assert_eq!(text(&jim), "ax\nb\nax\nax\n");
+        assert!(jim.properties.prompt.is_none());
+        let message = jim.properties.messages.back().unwrap();
+        assert_eq!(message.text, "Cannot ask for confirmation inside :global");
+    }
+
+    #[test]
+    fn plus_search_takes_a_regex_and_becomes_the_last_search() {
+        let mut jim = jim("x\nFOxO\nfoyo\n");
+        jim.properties.go_to_first_match("fo.o");
+        assert_eq!(jim.properties.cursor.index, 2);
+        press(&mut jim, "n");
+        assert_eq!(jim.properties.cursor.index, 7);
+        assert_eq!(jim.properties.search.pattern.as_deref(), Some("fo.o"));
+
+        jim.properties.go_to_first_match("Fo.o");
+        assert_eq!(jim.properties.cursor.index, 7);
+        let message = jim.properties.messages.back().unwrap();
+        assert_eq!(message.text, "Pattern not found: Fo.o");
+    }
+
+    #[test]
+    fn one_file_opened_by_different_paths_is_one_buffer() {
+        let directory = std::env::temp_dir().join(format!("jim-{}-open", std::process::id()));
+        std::fs::create_dir_all(directory.join("sub")).unwrap();
+        std::fs::write(directory.join("x"), "x\n").unwrap();
+        let mut jim = jim("");
+        jim.properties.open_file(&directory.join("x")).unwrap();
+        jim.properties.open_file(&directory.join("./x")).unwrap();
+        jim.properties
+            .open_file(&directory.join("sub/../x"))
+            .unwrap();
+        assert_eq!(jim.properties.buffers.len(), 2);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn global_substitutes_every_matching_line() {
+        let mut jim = jim("ax\nb\nax\nax\n");
+        press(&mut jim, ":g/a/s/x/y/<CR>");
+        assert_eq!(text(&jim), "ay\nb\nay\nay\n");
+    }
+
+    #[test]
+    fn commands_sharing_an_abbreviation_are_ambiguous() {
+        let commands = [
+            Command::new("bn[ext]", |_, _| Ok(())),
+            Command::new("bN[ext]", |_, _| Ok(())),
+            Command::new("b[uffer]", |_, _| Ok(())),
+            Command::new("bne", |_, _| Ok(())),
+        ];
+        assert_eq!(find_command(&commands, "bn").unwrap().name(), "bnext");
+        assert_eq!(find_command(&commands, "bu").unwrap().name(), "buffer");
+        assert_eq!(
+            find_command(&commands, "bne").err(),
+            Some("Ambiguous command: bne".to_string())
+        );
+        assert!(find_command(&commands, "x").is_err());
     }
 
-    fn backspace_command(&mut self) {
-        self.command.pop();
+    #[test]
+    fn block_change_types_on_every_line_of_the_block() {
+        let mut jim = jim("abc\ndef\ng\nhij\n");
+        press(&mut jim, "l");
+        jim.handle_key(KeyEvent::new(KeyCode::Char('v'), KeyModifiers::CONTROL));
+        press(&mut jim, "jjjlcXY");
+        jim.handle_key(KeyEvent::from(KeyCode::Esc));
+        assert_eq!(text(&jim), "aXY\ndXY\ng\nhXY\n");
+        press(&mut jim, "u");
+        assert_eq!(text(&jim), "abc\ndef\ng\nhij\n");
     }
 }
@@ -1,5 +1,5 @@
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
+    event::{DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -9,12 +9,30 @@ use tui::{
     Terminal,
 };
 
+pub mod buffer;
 pub mod builtin_maps;
+pub mod cli;
+pub mod clipboard;
+pub mod command_line;
 pub mod cursor;
+pub mod diff;
+pub mod ex;
+pub mod explorer;
 pub mod file;
+pub mod history;
 pub mod jim;
 pub mod mapping;
+pub mod message;
 pub mod mode;
+pub mod motion;
+pub mod operator;
+pub mod options;
+pub mod register;
+pub mod search;
+pub mod selection;
+pub mod substitute;
+pub mod watcher;
+pub mod window;
 
 fn main() -> Result<(), Box<dyn Error>> {
     run()
@@ -24,7 +42,12 @@ pub fn run() -> Result<(), Box<dyn Error>> {
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableFocusChange
+    )?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
@@ -35,7 +58,8 @@ pub fn run() -> Result<(), Box<dyn Error>> {
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableFocusChange
     )?;
     terminal.show_cursor()?;
 
@@ -51,6 +75,8 @@ fn create_jim<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
         .init()?
         .add_nmaps(builtin_maps::nmaps)
         .add_imaps(builtin_maps::imaps)
+        .add_vmaps(builtin_maps::vmaps)
+        .add_omaps(builtin_maps::omaps)
         .add_cmaps(builtin_maps::cmaps)
         .run(terminal)
 }
@@ -1,31 +1,100 @@
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
-use crate::jim::JimProperties;
+use crate::{
+    ex::ExCommand,
+    jim::{Jim, JimProperties},
+    motion::MotionKind,
+};
 
 pub struct Mapping {
-    buttons: Vec<KeyCode>,
+    buttons: Vec<KeyEvent>,
     wait_for_next_press: bool,
-    already_pressed: bool,
-    on_pressed: fn(&mut JimProperties),
+    motion: Option<MotionKind>,
+    on_pressed: fn(&mut JimProperties, MapContext),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyMatch {
+    Full,
+    Partial,
+    None,
+}
+
+/// What a mapping was invoked with besides its keys.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MapContext {
+    pub count: Option<usize>,
+    pub register: Option<char>,
+}
+
+impl MapContext {
+    /// The count typed before the mapping, 1 if there was none.
+    pub fn count(&self) -> usize {
+        self.count.unwrap_or(1)
+    }
 }
 
 pub struct Command {
     name: String,
-    on_presed: fn(&mut JimProperties),
+    /// How much of the name has to be typed.
+    shortest: usize,
+    takes_range: bool,
+    action: CommandAction,
+}
+
+/// What a command runs. Commands that run other commands or type keys,
+/// like `:g` and `:normal`, need the whole editor and its mappings.
+#[derive(Clone, Copy)]
+pub enum CommandAction {
+    Properties(fn(&mut JimProperties, &ExCommand) -> Result<(), String>),
+    Editor(fn(&mut Jim, &ExCommand) -> Result<(), String>),
 }
 
 impl Command {
-    pub fn new(name: &str, on_presed: fn(&mut JimProperties)) -> Self {
+    /// `name` marks the shortest abbreviation like vim's help does, `q[uit]`
+    /// runs for `:q`, `:qui` and `:quit`.
+    pub fn new(
+        name: &str,
+        on_presed: fn(&mut JimProperties, &ExCommand) -> Result<(), String>,
+    ) -> Self {
+        Self::with_action(name, CommandAction::Properties(on_presed))
+    }
+
+    /// A command that runs with the whole editor.
+    pub fn editor(name: &str, on_presed: fn(&mut Jim, &ExCommand) -> Result<(), String>) -> Self {
+        Self::with_action(name, CommandAction::Editor(on_presed))
+    }
+
+    fn with_action(name: &str, action: CommandAction) -> Self {
         Self {
-            name: name.to_string(),
-            on_presed,
+            name: name.replace(['[', ']'], ""),
+            shortest: name.find('[').unwrap_or(name.len()),
+            takes_range: false,
+            action,
         }
     }
 
-    pub fn try_use(&mut self, properties: &mut JimProperties, command_string: &str) {
-        if &self.name == command_string {
-            (self.on_presed)(properties)
+    /// Lets the command be given a range, it's an error otherwise.
+    pub fn with_range(mut self) -> Self {
+        self.takes_range = true;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether `name` is long enough a start of this command's name.
+    pub fn matches(&self, name: &str) -> bool {
+        name.len() >= self.shortest && self.name.starts_with(name)
+    }
+
+    /// What to run for `command`, checking first that it can have its range.
+    pub fn action(&self, command: &ExCommand) -> Result<CommandAction, String> {
+        if command.range.is_some() && !self.takes_range {
+            return Err("No range allowed".to_string());
         }
+        Ok(self.action)
     }
 }
 
@@ -45,73 +114,150 @@ impl std::fmt::Debug for Mapping {
 
 impl Mapping {
     pub fn new(
-        buttons: Vec<KeyCode>,
-        on_pressed: fn(&mut JimProperties),
+        buttons: Vec<KeyEvent>,
+        on_pressed: fn(&mut JimProperties, MapContext),
         wait_for_next_press: bool,
     ) -> Self {
         Self {
             buttons,
             on_pressed,
             wait_for_next_press,
-            already_pressed: false,
+            motion: None,
         }
     }
 
-    pub fn try_use(&mut self, properties: &mut JimProperties) {
-        if properties.cant_press_maps && !self.already_pressed {
-            return;
-        }
-        if properties.use_mapping(&self.buttons) || self.already_pressed {
-            if !self.wait_for_next_press || self.already_pressed {
-                self.already_pressed = false;
-                properties.cant_press_maps = false;
-                (self.on_pressed)(properties);
-            } else if self.wait_for_next_press {
-                self.already_pressed = true;
-                properties.cant_press_maps = true;
-            }
-            properties.buttons_pressed.clear();
+    /// Marks the mapping as a motion, so operators can act on the text it
+    /// moves over.
+    pub fn with_motion(mut self, kind: MotionKind) -> Self {
+        self.motion = Some(kind);
+        self
+    }
+
+    pub fn motion(&self) -> Option<MotionKind> {
+        self.motion
+    }
+
+    pub fn on_pressed(&self) -> fn(&mut JimProperties, MapContext) {
+        self.on_pressed
+    }
+
+    /// Whether `pressed` triggers this mapping, or could once more keys
+    /// are pressed. Mappings that wait for the next press take any key.
+    pub fn matches(&self, pressed: &[KeyEvent]) -> KeyMatch {
+        let length = self.buttons.len() + self.wait_for_next_press as usize;
+        let compared = pressed.len().min(self.buttons.len());
+        if pressed.len() > length || pressed[..compared] != self.buttons[..compared] {
+            KeyMatch::None
+        } else if pressed.len() == length {
+            KeyMatch::Full
+        } else {
+            KeyMatch::Partial
         }
     }
 }
 
 pub trait ToMapping {
-    fn to_mapping(&self, on_pressed: fn(&mut JimProperties), wait_for_next_press: bool) -> Mapping;
+    fn to_mapping(
+        &self,
+        on_pressed: fn(&mut JimProperties, MapContext),
+        wait_for_next_press: bool,
+    ) -> Mapping;
+
+    fn to_motion(
+        &self,
+        on_pressed: fn(&mut JimProperties, MapContext),
+        kind: MotionKind,
+        wait_for_next_press: bool,
+    ) -> Mapping {
+        self.to_mapping(on_pressed, wait_for_next_press)
+            .with_motion(kind)
+    }
 }
 
 impl ToMapping for str {
-    fn to_mapping(&self, on_pressed: fn(&mut JimProperties), wait_for_next_press: bool) -> Mapping {
+    fn to_mapping(
+        &self,
+        on_pressed: fn(&mut JimProperties, MapContext),
+        wait_for_next_press: bool,
+    ) -> Mapping {
         let mut buttons = vec![];
 
         let mut special_mode = false;
-        let mut special_chars = vec![];
+        let mut special_chars = String::new();
 
         for char in self.chars() {
             if special_mode {
                 if char == '>' {
                     special_mode = false;
+                    buttons.push(parse_special_key(&special_chars));
                     special_chars.clear();
-                }
-
-                special_chars.push(char);
-                if special_chars == vec!['l', 'C', 'r'] {
-                    buttons.push(KeyCode::Modifier(
-                        crossterm::event::ModifierKeyCode::LeftControl,
-                    ));
-                } else if special_chars == vec!['r', 'C', 'r'] {
-                    buttons.push(KeyCode::Modifier(
-                        crossterm::event::ModifierKeyCode::RightControl,
-                    ));
-                } else if special_chars == vec!['E', 's', 'c'] {
-                    buttons.push(KeyCode::Esc);
+                } else {
+                    special_chars.push(char);
                 }
             } else if char == '<' {
                 special_mode = true;
             } else {
-                buttons.push(KeyCode::Char(char));
+                buttons.push(KeyEvent::from(KeyCode::Char(char)));
             }
         }
 
         Mapping::new(buttons, on_pressed, wait_for_next_press)
     }
 }
+
+/// Parses the inside of a `<...>` key name, e.g. `Esc`, `CR` or `C-r`.
+fn parse_special_key(name: &str) -> KeyEvent {
+    if let Some(key) = name.strip_prefix("C-") {
+        let code = parse_special_key(key).code;
+        return KeyEvent::new(code, KeyModifiers::CONTROL);
+    }
+
+    let code = match name {
+        "Esc" => KeyCode::Esc,
+        "CR" | "Enter" => KeyCode::Enter,
+        "Tab" => KeyCode::Tab,
+        "BS" => KeyCode::Backspace,
+        "Space" => KeyCode::Char(' '),
+        "lt" => KeyCode::Char('<'),
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        _ => KeyCode::Char(name.chars().next().unwrap_or(' ')),
+    };
+    KeyEvent::from(code)
+}
+
+/// Drops the shift modifier from characters, the case of the char already
+/// says whether shift was held.
+pub fn normalize_key(mut key: KeyEvent) -> KeyEvent {
+    if let KeyCode::Char(_) = key.code {
+        key.modifiers.remove(KeyModifiers::SHIFT);
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(name: &str) -> Command {
+        Command::new(name, |_, _| Ok(()))
+    }
+
+    #[test]
+    fn commands_match_their_abbreviations() {
+        let quit = command("q[uit]");
+        assert_eq!(quit.name(), "quit");
+        assert!(quit.matches("q"));
+        assert!(quit.matches("qui"));
+        assert!(quit.matches("quit"));
+        assert!(!quit.matches("quits"));
+        assert!(!quit.matches("qa"));
+
+        let bnext = command("bn[ext]");
+        assert!(!bnext.matches("b"));
+        assert!(bnext.matches("bn"));
+        assert!(!bnext.matches(""));
+    }
+}
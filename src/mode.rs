@@ -1,7 +1,35 @@
-#[derive(Debug, Default, PartialEq, Eq)]
+use crate::selection::SelectionKind;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum Mode {
     #[default]
     Normal,
     Insert,
     Command,
+    /// Typing a `/` or `?` search.
+    Search,
+    Visual,
+    VisualLine,
+    VisualBlock,
+    OperatorPending,
+}
+
+impl Mode {
+    pub fn selection_kind(&self) -> Option<SelectionKind> {
+        match self {
+            Mode::Visual => Some(SelectionKind::Characterwise),
+            Mode::VisualLine => Some(SelectionKind::Linewise),
+            Mode::VisualBlock => Some(SelectionKind::Blockwise),
+            _ => None,
+        }
+    }
+
+    pub fn is_visual(&self) -> bool {
+        self.selection_kind().is_some()
+    }
+
+    /// Whether keys go to the line at the bottom of the screen.
+    pub fn is_command_line(&self) -> bool {
+        matches!(self, Mode::Command | Mode::Search)
+    }
 }
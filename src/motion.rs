@@ -0,0 +1,222 @@
+use std::ops::Range;
+
+use crate::buffer::Buffer;
+
+/// How an operator treats the text between the cursor and the end of a
+/// motion, see `:help exclusive` in vim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionKind {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Blank,
+    Punctuation,
+    Word,
+}
+
+fn char_class(char: char) -> CharClass {
+    if char.is_whitespace() {
+        CharClass::Blank
+    } else if char.is_alphanumeric() || char == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+fn class_at(contents: &Buffer, index: usize) -> CharClass {
+    contents.char(index).map_or(CharClass::Blank, char_class)
+}
+
+fn is_empty_line_at(contents: &Buffer, index: usize) -> bool {
+    contents.char(index) == Some('\n') && (index == 0 || contents.char(index - 1) == Some('\n'))
+}
+
+/// Start of the next word, like vim's `w`. Empty lines count as words.
+pub fn next_word_start(contents: &Buffer, mut index: usize) -> usize {
+    let length = contents.len_chars();
+    let class = class_at(contents, index);
+    if class != CharClass::Blank {
+        while index < length && class_at(contents, index) == class {
+            index += 1;
+        }
+    } else if is_empty_line_at(contents, index) {
+        index += 1;
+    }
+
+    while index < length && class_at(contents, index) == CharClass::Blank {
+        if is_empty_line_at(contents, index) {
+            break;
+        }
+        index += 1;
+    }
+    index
+}
+
+/// End of the current or next word, like vim's `e`.
+pub fn word_end(contents: &Buffer, mut index: usize) -> usize {
+    let length = contents.len_chars();
+    index += 1;
+    while index < length && class_at(contents, index) == CharClass::Blank {
+        index += 1;
+    }
+    if index >= length {
+        return length.saturating_sub(1);
+    }
+
+    let class = class_at(contents, index);
+    while index + 1 < length && class_at(contents, index + 1) == class {
+        index += 1;
+    }
+    index
+}
+
+/// The last char of the word under `index`, where `cw` changes to.
+pub fn current_word_end(contents: &Buffer, mut index: usize) -> usize {
+    let length = contents.len_chars();
+    let class = class_at(contents, index);
+    while index + 1 < length && class_at(contents, index + 1) == class {
+        index += 1;
+    }
+    index
+}
+
+/// Start of the current or previous word, like vim's `b`.
+pub fn previous_word_start(contents: &Buffer, mut index: usize) -> usize {
+    if index == 0 {
+        return 0;
+    }
+    index -= 1;
+    while index > 0 && class_at(contents, index) == CharClass::Blank {
+        if is_empty_line_at(contents, index) {
+            return index;
+        }
+        index -= 1;
+    }
+
+    let class = class_at(contents, index);
+    while index > 0 && class_at(contents, index - 1) == class {
+        index -= 1;
+    }
+    index
+}
+
+/// The word (or run of blanks) under `index` for `iw`, with the blanks
+/// after it, or before it if there are none after, for `aw`.
+pub fn word_object(contents: &Buffer, index: usize, around: bool) -> Range<usize> {
+    let run = |index: usize| {
+        let class = class_at(contents, index);
+        let same =
+            |index: usize| contents.char(index) != Some('\n') && class_at(contents, index) == class;
+        let mut start = index;
+        while start > 0 && same(start - 1) {
+            start -= 1;
+        }
+        let mut end = index;
+        while same(end) {
+            end += 1;
+        }
+        start..end
+    };
+
+    if contents.char(index).is_none_or(|char| char == '\n') {
+        return index..index;
+    }
+    let word = run(index);
+    if !around {
+        return word;
+    }
+
+    if class_at(contents, word.start) == CharClass::Blank {
+        let next = run(word.end);
+        return word.start..next.end.max(word.end);
+    }
+    let after = run(word.end);
+    if word.end < after.end && class_at(contents, word.end) == CharClass::Blank {
+        return word.start..after.end;
+    }
+    if word.start > 0 && class_at(contents, word.start - 1) == CharClass::Blank {
+        return run(word.start - 1).start..word.end;
+    }
+    word
+}
+
+/// The text inside the nearest `open`/`close` pair around `index`, with
+/// the pair itself when `around` is set.
+pub fn pair_object(
+    contents: &Buffer,
+    index: usize,
+    open: char,
+    close: char,
+    around: bool,
+) -> Option<Range<usize>> {
+    let mut depth = 0;
+    let mut start = index;
+    loop {
+        match contents.char(start)? {
+            char if char == close && start != index => depth += 1,
+            char if char == open => {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+        start = start.checked_sub(1)?;
+    }
+
+    let mut depth = 0;
+    let mut end = start + 1;
+    loop {
+        match contents.char(end)? {
+            char if char == open => depth += 1,
+            char if char == close => {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+        end += 1;
+    }
+
+    if around {
+        Some(start..end + 1)
+    } else {
+        Some(start + 1..end)
+    }
+}
+
+/// The quoted string on the line around or after `index`.
+pub fn quote_object(
+    contents: &Buffer,
+    index: usize,
+    quote: char,
+    around: bool,
+) -> Option<Range<usize>> {
+    let line = contents.char_to_line(index);
+    let line_start = contents.line_to_char(line);
+    let quotes: Vec<usize> = contents
+        .line(line)?
+        .chars()
+        .enumerate()
+        .filter(|&(_, char)| char == quote)
+        .map(|(column, _)| line_start + column)
+        .collect();
+
+    let (open, close) = quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|&(_, close)| index <= close)?;
+    if around {
+        Some(open..close + 1)
+    } else {
+        Some(open + 1..close)
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{
+    jim::JimProperties,
+    message::Message,
+    mode::Mode,
+    register::Register,
+    selection::{Selection, SelectionKind},
+};
+
+const SHIFT_WIDTH: usize = 4;
+
+/// A blockwise change waiting for insert mode to end, what was typed on the
+/// first line of the block is then typed on the other lines too.
+#[derive(Debug)]
+pub struct BlockInsert {
+    /// Where typing started on the first line.
+    pub start: usize,
+    pub column: usize,
+    /// The other lines that reached the block.
+    pub lines: Vec<usize>,
+}
+
+impl BlockInsert {
+    /// Types the text between `start` and `end` on the other lines, unless
+    /// it went past the first line.
+    pub fn finish(self, jim: &mut JimProperties, end: usize) {
+        let buffer = jim.get_mut_current_buffer();
+        if end <= self.start {
+            return;
+        }
+        let typed = buffer.contents.slice(self.start..end).to_string();
+        if typed.contains('\n') {
+            return;
+        }
+        for line in self.lines {
+            let line_start = buffer.contents.line_to_char(line);
+            buffer.insert(line_start + self.column, &typed);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Outdent,
+    ToggleCase,
+}
+
+impl Operator {
+    /// The key that repeats the operator to act on whole lines, as in `dd`.
+    pub fn line_key(self) -> char {
+        match self {
+            Operator::Delete => 'd',
+            Operator::Change => 'c',
+            Operator::Yank => 'y',
+            Operator::Indent => '>',
+            Operator::Outdent => '<',
+            Operator::ToggleCase => '~',
+        }
+    }
+
+    /// Applies the operator to `selection`, deleted or yanked text goes to
+    /// `register`.
+    pub fn apply(self, jim: &mut JimProperties, selection: Selection, register: Option<char>) {
+        jim.mode = Mode::Normal;
+        match self {
+            Operator::Delete => delete(jim, &selection, register),
+            Operator::Change => change(jim, &selection, register),
+            Operator::Yank => {
+                let yanked = selected_text(jim, &selection);
+                if let Err(error) = jim.registers.yank(register, yanked) {
+                    jim.show(Message::error(format!(
+                        "Could not copy to the clipboard: {error}"
+                    )));
+                }
+            }
+            Operator::Indent => indent(jim, &selection),
+            Operator::Outdent => outdent(jim, &selection),
+            Operator::ToggleCase => toggle_case(jim, &selection),
+        }
+
+        if self != Operator::Change {
+            move_to_start(jim, &selection);
+        }
+    }
+}
+
+fn selected_text(jim: &JimProperties, selection: &Selection) -> Register {
+    let contents = jim.get_current_buffer_contents();
+    let mut text = selection
+        .ranges(jim.get_current_buffer())
+        .into_iter()
+        .map(|range| contents.slice(range).to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if selection.kind == SelectionKind::Linewise && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    Register::new(text, selection.kind)
+}
+
+fn delete(jim: &mut JimProperties, selection: &Selection, register: Option<char>) {
+    let deleted = selected_text(jim, selection);
+    if let Err(error) = jim.registers.delete(register, deleted) {
+        jim.show(Message::error(format!(
+            "Could not copy to the clipboard: {error}"
+        )));
+    }
+
+    let buffer = jim.get_mut_current_buffer();
+    for mut range in selection.ranges(buffer).into_iter().rev() {
+        // Deleting the last lines also takes the line break before them,
+        // otherwise an empty line would be left behind.
+        if selection.kind == SelectionKind::Linewise
+            && range.end == buffer.contents.len_chars()
+            && range.start > 0
+            && buffer.contents.char(range.end - 1) != Some('\n')
+        {
+            range.start -= 1;
+        }
+        buffer.remove(range);
+    }
+}
+
+fn change(jim: &mut JimProperties, selection: &Selection, register: Option<char>) {
+    let deleted = selected_text(jim, selection);
+    if let Err(error) = jim.registers.delete(register, deleted) {
+        jim.show(Message::error(format!(
+            "Could not copy to the clipboard: {error}"
+        )));
+    }
+
+    let buffer = jim.get_mut_current_buffer();
+    let ranges = selection.ranges(buffer);
+    // Lines too short to reach the block are left alone.
+    let block_lines: Vec<usize> = ranges
+        .iter()
+        .map(|range| buffer.contents.char_to_line(range.start))
+        .filter(|&line| {
+            line != selection.first_line
+                && buffer.contents.line_len(line).unwrap_or_default() > selection.left_column
+        })
+        .collect();
+    for mut range in ranges.into_iter().rev() {
+        // Changing lines keeps an empty line to type into.
+        if selection.kind == SelectionKind::Linewise
+            && buffer.contents.char(range.end.saturating_sub(1)) == Some('\n')
+        {
+            range.end -= 1;
+        }
+        buffer.remove(range);
+    }
+
+    move_to_start(jim, selection);
+    jim.block_insert = (selection.kind == SelectionKind::Blockwise).then_some(BlockInsert {
+        start: jim.cursor.index,
+        column: selection.left_column,
+        lines: block_lines,
+    });
+    jim.mode = Mode::Insert;
+}
+
+fn indent(jim: &mut JimProperties, selection: &Selection) {
+    let buffer = jim.get_mut_current_buffer();
+    for line in selection.first_line..=selection.last_line {
+        if buffer.contents.line_len(line).unwrap_or_default() > 0 {
+            let line_start = buffer.contents.line_to_char(line);
+            buffer.insert(line_start, &" ".repeat(SHIFT_WIDTH));
+        }
+    }
+}
+
+fn outdent(jim: &mut JimProperties, selection: &Selection) {
+    let buffer = jim.get_mut_current_buffer();
+    for line in selection.first_line..=selection.last_line {
+        let Some(text) = buffer.contents.line(line) else {
+            continue;
+        };
+        let width = match text.chars().next() {
+            Some('\t') => 1,
+            _ => text
+                .chars()
+                .take(SHIFT_WIDTH)
+                .take_while(|&char| char == ' ')
+                .count(),
+        };
+        let line_start = buffer.contents.line_to_char(line);
+        if width > 0 {
+            buffer.remove(line_start..line_start + width);
+        }
+    }
+}
+
+fn toggle_case(jim: &mut JimProperties, selection: &Selection) {
+    let buffer = jim.get_mut_current_buffer();
+    for range in selection.ranges(buffer).into_iter().rev() {
+        let text = buffer.contents.slice(range.clone()).to_string();
+        let toggled: String = text
+            .chars()
+            .flat_map(|char| {
+                if char.is_uppercase() {
+                    char.to_lowercase().collect::<Vec<_>>()
+                } else {
+                    char.to_uppercase().collect()
+                }
+            })
+            .collect();
+        if toggled != text {
+            buffer.remove(range.clone());
+            buffer.insert(range.start, &toggled);
+        }
+    }
+}
+
+fn move_to_start(jim: &mut JimProperties, selection: &Selection) {
+    let contents = jim.get_current_buffer_contents();
+    let index = match selection.kind {
+        SelectionKind::Characterwise => selection.start,
+        SelectionKind::Linewise => {
+            let last_line = contents.len_lines().saturating_sub(1);
+            contents.line_to_char(selection.first_line.min(last_line))
+        }
+        SelectionKind::Blockwise => {
+            let length = contents.line_len(selection.first_line).unwrap_or_default();
+            contents.line_to_char(selection.first_line) + selection.left_column.min(length)
+        }
+    };
+    jim.set_cursor_index(index);
+}
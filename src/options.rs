@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+/// Settings, named after the vim options they copy.
+#[derive(Debug)]
+pub struct Options {
+    /// Lines kept visible above and below the cursor.
+    pub scrolloff: usize,
+    /// Columns kept visible to the left and right of the cursor.
+    pub sidescrolloff: usize,
+    /// Copy the file to a `~` backup before overwriting it.
+    pub backup: bool,
+    /// Where backups go, next to the file when unset.
+    pub backupdir: Option<PathBuf>,
+    /// Reload unmodified buffers whose file changed on disk.
+    pub autoread: bool,
+    /// Searches ignore case. Unlike vim this and 'smartcase' start on.
+    pub ignorecase: bool,
+    /// Patterns with capitals in them don't ignore case after all.
+    pub smartcase: bool,
+    /// Searches go on from the other end of the buffer.
+    pub wrapscan: bool,
+    /// Show where a search goes while it's typed.
+    pub incsearch: bool,
+    /// Buffers with up to this many lines keep their undo history when
+    /// they're reloaded.
+    pub undoreload: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            scrolloff: 5,
+            sidescrolloff: 0,
+            backup: false,
+            backupdir: None,
+            autoread: true,
+            ignorecase: true,
+            smartcase: true,
+            wrapscan: true,
+            incsearch: true,
+            undoreload: 10000,
+        }
+    }
+}
+
+impl Options {
+    /// Applies one `:set` argument: `name` or `noname` for flags and
+    /// `name=value` for the rest.
+    pub fn set(&mut self, argument: &str) -> Result<(), String> {
+        let number = |value: &str| {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid number: {argument}"))
+        };
+        match argument.split_once('=') {
+            Some(("scrolloff" | "so", value)) => self.scrolloff = number(value)?,
+            Some(("sidescrolloff" | "siso", value)) => self.sidescrolloff = number(value)?,
+            Some(("undoreload" | "ur", value)) => self.undoreload = number(value)?,
+            Some(("backupdir" | "bdir", "")) => self.backupdir = None,
+            Some(("backupdir" | "bdir", value)) => self.backupdir = Some(PathBuf::from(value)),
+            None if matches!(argument, "backup" | "bk") => self.backup = true,
+            None if matches!(argument, "nobackup" | "nobk") => self.backup = false,
+            None if matches!(argument, "autoread" | "ar") => self.autoread = true,
+            None if matches!(argument, "noautoread" | "noar") => self.autoread = false,
+            None if matches!(argument, "ignorecase" | "ic") => self.ignorecase = true,
+            None if matches!(argument, "noignorecase" | "noic") => self.ignorecase = false,
+            None if matches!(argument, "smartcase" | "scs") => self.smartcase = true,
+            None if matches!(argument, "nosmartcase" | "noscs") => self.smartcase = false,
+            None if matches!(argument, "wrapscan" | "ws") => self.wrapscan = true,
+            None if matches!(argument, "nowrapscan" | "nows") => self.wrapscan = false,
+            None if matches!(argument, "incsearch" | "is") => self.incsearch = true,
+            None if matches!(argument, "noincsearch" | "nois") => self.incsearch = false,
+            _ => return Err(format!("Unknown option: {argument}")),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+};
+
+use crate::{
+    clipboard::{Clipboard, SystemSelection},
+    selection::SelectionKind,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Register {
+    pub text: String,
+    pub kind: SelectionKind,
+}
+
+impl Register {
+    pub fn new(text: String, kind: SelectionKind) -> Self {
+        Self { text, kind }
+    }
+
+    /// Adds `other` to the end, as writing to `A`-`Z` does.
+    fn append(&mut self, other: Register) {
+        if self.kind == SelectionKind::Linewise || other.kind == SelectionKind::Linewise {
+            if !self.text.ends_with('\n') {
+                self.text.push('\n');
+            }
+            self.kind = SelectionKind::Linewise;
+        } else if self.kind == SelectionKind::Blockwise || other.kind == SelectionKind::Blockwise {
+            self.text.push('\n');
+        }
+        self.text.push_str(&other.text);
+    }
+}
+
+/// Vim's registers. `%` is left to `JimProperties` since it needs to know
+/// the current buffer, `+` and `*` go through the clipboard.
+#[derive(Debug, Default)]
+pub struct Registers {
+    unnamed: Option<Register>,
+    yanked: Option<Register>,
+    /// `"1` to `"9`, a register written by name can leave earlier ones
+    /// empty.
+    deleted: VecDeque<Option<Register>>,
+    small_delete: Option<Register>,
+    named: HashMap<char, Register>,
+    pub last_inserted: String,
+    pub last_command: String,
+    inserting: String,
+    pub clipboard: Clipboard,
+    /// What went to each selection last, a paste of the same text gets its
+    /// kind back.
+    copied: HashMap<SystemSelection, Register>,
+}
+
+const NUMBERED_REGISTERS: usize = 9;
+
+impl Registers {
+    pub fn is_valid_name(name: char) -> bool {
+        name.is_ascii_alphanumeric() || "\"-_.:%+*".contains(name)
+    }
+
+    pub fn is_read_only(name: char) -> bool {
+        ".:%".contains(name)
+    }
+
+    pub fn get(&self, name: char) -> Option<Register> {
+        match name {
+            '"' => self.unnamed.clone(),
+            '0' => self.yanked.clone(),
+            '1'..='9' => {
+                let index = name.to_digit(10).unwrap() as usize - 1;
+                self.deleted.get(index).cloned().flatten()
+            }
+            '-' => self.small_delete.clone(),
+            'a'..='z' | 'A'..='Z' => self.named.get(&name.to_ascii_lowercase()).cloned(),
+            '.' => Self::from_text(&self.last_inserted),
+            ':' => Self::from_text(&self.last_command),
+            '+' | '*' => {
+                let selection = SystemSelection::from_register(name)?;
+                let text = self.clipboard.paste(selection).ok()?;
+                if let Some(copied) = self.copied.get(&selection) {
+                    if copied.text == text {
+                        return Some(copied.clone());
+                    }
+                }
+                let kind = if text.ends_with('\n') {
+                    SelectionKind::Linewise
+                } else {
+                    SelectionKind::Characterwise
+                };
+                (!text.is_empty()).then(|| Register::new(text, kind))
+            }
+            _ => None,
+        }
+    }
+
+    /// Stores yanked text in `name`, or `"0` if no register was given.
+    pub fn yank(&mut self, name: Option<char>, register: Register) -> io::Result<()> {
+        match name {
+            Some(name) if name != '"' => self.write_named(name, register),
+            _ => {
+                self.yanked = Some(register.clone());
+                self.unnamed = Some(register);
+                Ok(())
+            }
+        }
+    }
+
+    /// Stores deleted text in `name`, or shifts it into the numbered
+    /// registers if it spans lines and into `"-` if it doesn't.
+    pub fn delete(&mut self, name: Option<char>, register: Register) -> io::Result<()> {
+        match name {
+            Some(name) if name != '"' => self.write_named(name, register),
+            _ => {
+                if register.kind == SelectionKind::Characterwise && !register.text.contains('\n') {
+                    self.small_delete = Some(register.clone());
+                } else {
+                    self.deleted.push_front(Some(register.clone()));
+                    self.deleted.truncate(NUMBERED_REGISTERS);
+                }
+                self.unnamed = Some(register);
+                Ok(())
+            }
+        }
+    }
+
+    fn write_named(&mut self, name: char, register: Register) -> io::Result<()> {
+        if name == '_' || Self::is_read_only(name) {
+            return Ok(());
+        }
+        if let Some(selection) = SystemSelection::from_register(name) {
+            self.clipboard.copy(selection, &register.text)?;
+            self.copied.insert(selection, register.clone());
+            self.unnamed = Some(register);
+            return Ok(());
+        }
+        match name {
+            '0' => {
+                self.yanked = Some(register.clone());
+                self.unnamed = Some(register);
+                return Ok(());
+            }
+            '1'..='9' => {
+                let index = name.to_digit(10).unwrap() as usize - 1;
+                if self.deleted.len() <= index {
+                    self.deleted.resize(index + 1, None);
+                }
+                self.deleted[index] = Some(register.clone());
+                self.unnamed = Some(register);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        if name.is_ascii_uppercase() {
+            let lowercase = name.to_ascii_lowercase();
+            match self.named.get_mut(&lowercase) {
+                Some(existing) => existing.append(register),
+                None => {
+                    self.named.insert(lowercase, register);
+                }
+            }
+        } else if name.is_ascii_lowercase() {
+            self.named.insert(name, register);
+        } else {
+            return Ok(());
+        }
+        self.unnamed = self.named.get(&name.to_ascii_lowercase()).cloned();
+        Ok(())
+    }
+
+    pub fn record_insert(&mut self, char: char) {
+        self.inserting.push(char);
+    }
+
+    pub fn record_backspace(&mut self) {
+        self.inserting.pop();
+    }
+
+    /// Ends an insert session, keeping what was typed for `".`.
+    pub fn finish_insert(&mut self) {
+        if !self.inserting.is_empty() {
+            self.last_inserted = std::mem::take(&mut self.inserting);
+        }
+    }
+
+    /// Every register holding something, in the order `:registers` lists
+    /// them.
+    pub fn list(&self) -> Vec<(char, Register)> {
+        let names = ['"', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9']
+            .into_iter()
+            .chain('a'..='z')
+            .chain(['-', '.', ':', '+', '*']);
+        names
+            .filter_map(|name| Some((name, self.get(name)?)))
+            .collect()
+    }
+
+    fn from_text(text: &str) -> Option<Register> {
+        if text.is_empty() {
+            return None;
+        }
+        Some(Register::new(
+            text.to_string(),
+            SelectionKind::Characterwise,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clipboard::FakeClipboard;
+
+    fn register(text: &str, kind: SelectionKind) -> Register {
+        Register::new(text.to_string(), kind)
+    }
+
+    fn registers_with(clipboard: &FakeClipboard) -> Registers {
+        Registers {
+            clipboard: Clipboard::new(clipboard.clone()),
+            ..Default::default()
+        }
+    }
+
+    fn registers() -> Registers {
+        registers_with(&FakeClipboard::default())
+    }
+
+    #[test]
+    fn numbered_registers_can_be_written_by_name() {
+        let mut registers = registers();
+        let line = register("three\n", SelectionKind::Linewise);
+        registers.yank(Some('3'), line.clone()).unwrap();
+        assert_eq!(registers.get('3'), Some(line.clone()));
+        assert_eq!(registers.get('1'), None);
+        assert_eq!(registers.get('"'), Some(line));
+
+        let word = register("zero", SelectionKind::Characterwise);
+        registers.yank(Some('0'), word.clone()).unwrap();
+        assert_eq!(registers.get('0'), Some(word));
+    }
+
+    #[test]
+    fn deleting_lines_shifts_the_numbered_registers() {
+        let mut registers = registers();
+        let first = register("first\n", SelectionKind::Linewise);
+        let second = register("second\n", SelectionKind::Linewise);
+        registers.delete(None, first.clone()).unwrap();
+        registers.delete(None, second.clone()).unwrap();
+        registers
+            .delete(None, register("small", SelectionKind::Characterwise))
+            .unwrap();
+        assert_eq!(registers.get('1'), Some(second));
+        assert_eq!(registers.get('2'), Some(first));
+        assert_eq!(registers.get('-').unwrap().text, "small");
+    }
+
+    #[test]
+    fn uppercase_names_append() {
+        let mut registers = registers();
+        let word = |text: &str| register(text, SelectionKind::Characterwise);
+        registers.yank(Some('a'), word("one")).unwrap();
+        registers.yank(Some('A'), word(" two")).unwrap();
+        assert_eq!(registers.get('a'), Some(word("one two")));
+        registers
+            .yank(Some('A'), register("line\n", SelectionKind::Linewise))
+            .unwrap();
+        assert_eq!(
+            registers.get('a'),
+            Some(register("one two\nline\n", SelectionKind::Linewise))
+        );
+    }
+
+    #[test]
+    fn clipboard_keeps_the_kind_of_what_was_copied() {
+        let clipboard = FakeClipboard::default();
+        let mut registers = registers_with(&clipboard);
+        let block = register("ab\ncd", SelectionKind::Blockwise);
+        registers.yank(Some('+'), block.clone()).unwrap();
+        assert_eq!(registers.get('+'), Some(block));
+
+        // Once something else is copied the kind is guessed from the text.
+        clipboard.set(SystemSelection::Clipboard, "other\n");
+        assert_eq!(
+            registers.get('+'),
+            Some(register("other\n", SelectionKind::Linewise))
+        );
+    }
+}
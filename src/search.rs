@@ -0,0 +1,268 @@
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    buffer::Buffer, command_line::LineHistory, cursor::Cursor, mode::Mode, options::Options,
+};
+
+/// The last search, which `n` and `N` repeat, and the patterns searched
+/// for before it.
+#[derive(Debug)]
+pub struct Search {
+    /// The last pattern, from `/`, `?`, `*`, `#` or `:s`.
+    pub pattern: Option<String>,
+    /// `?` searches backwards, and so do the `n` after it.
+    pub backward: bool,
+    /// `*` and `#` ignore 'smartcase', vim keeps that for `n` too.
+    pub smartcase: bool,
+    /// Patterns typed at the `/` and `?` prompt.
+    pub history: LineHistory,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self {
+            pattern: None,
+            backward: false,
+            smartcase: true,
+            history: LineHistory::default(),
+        }
+    }
+}
+
+/// A `/` or `?` being typed.
+#[derive(Debug)]
+pub struct Prompt {
+    pub backward: bool,
+    /// The count before `/`, `3/x` goes to the third match.
+    pub count: usize,
+    /// Where the cursor was, the search starts there and `<Esc>` goes back.
+    pub origin: Cursor,
+    /// The mode to go back to, searching can move a visual selection or be
+    /// the motion of an operator.
+    pub mode: Mode,
+    /// The match the pattern typed so far would go to, with 'incsearch'.
+    pub preview: Option<Range<usize>>,
+    /// What 'incsearch' looks through on every key typed.
+    pub haystack: Haystack,
+}
+
+/// A match `find` went to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Found {
+    /// The match in chars.
+    pub range: Range<usize>,
+    /// Whether the search went around the end of the buffer to get there.
+    pub wrapped: bool,
+}
+
+/// The regex for a search pattern. Case is ignored with 'ignorecase',
+/// unless 'smartcase' is on too and the pattern has a capital in it.
+pub fn regex(pattern: &str, options: &Options, smartcase: bool) -> Result<Regex, String> {
+    let has_capital = pattern.chars().any(char::is_uppercase);
+    let ignore_case = options.ignorecase && !(smartcase && options.smartcase && has_capital);
+    RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .multi_line(true)
+        .build()
+        .map_err(|error| error.to_string())
+}
+
+/// The text of a buffer as one string, which is what the regex crate
+/// searches. Made once per command rather than once per match, the buffer
+/// can't change while a search is typed or repeated.
+#[derive(Debug)]
+pub struct Haystack {
+    text: String,
+}
+
+impl Haystack {
+    pub fn new(contents: &Buffer) -> Self {
+        Self {
+            text: contents.to_string(),
+        }
+    }
+
+    /// The first match in the buffer.
+    pub fn first(&self, contents: &Buffer, regex: &Regex) -> Option<Found> {
+        regex.find(&self.text).map(|first| Found {
+            range: contents.byte_to_char(first.start())..contents.byte_to_char(first.end()),
+            wrapped: false,
+        })
+    }
+
+    /// The first match after `index`, or before it when `backward`. With
+    /// `wrap` the search goes on from the other end of the buffer.
+    pub fn find(
+        &self,
+        contents: &Buffer,
+        regex: &Regex,
+        index: usize,
+        backward: bool,
+        wrap: bool,
+    ) -> Option<Found> {
+        let found = |found: regex::Match, wrapped: bool| Found {
+            range: contents.byte_to_char(found.start())..contents.byte_to_char(found.end()),
+            wrapped,
+        };
+
+        if backward {
+            let at = contents.char_to_byte(index);
+            let line = contents.char_to_line(index);
+            let before = self.last_before(contents, regex, 0..line + 1, at);
+            return match before {
+                Some(before) => Some(found(before, false)),
+                None if wrap => self
+                    .last_before(contents, regex, line..contents.len_lines(), self.text.len())
+                    .map(|last| found(last, true)),
+                None => None,
+            };
+        }
+
+        let after = contents.char_to_byte((index + 1).min(contents.len_chars()));
+        match regex.find_at(&self.text, after) {
+            Some(next) => Some(found(next, false)),
+            None if wrap => regex.find(&self.text).map(|first| found(first, true)),
+            None => None,
+        }
+    }
+
+    /// The last match starting on `lines` before the byte `before`. The
+    /// lines are looked at going up from the last in blocks that double in
+    /// size, so a match near the cursor is found without going through the
+    /// whole buffer, and the scan past the end of each block that finding
+    /// out there's no more matches in it takes happens only a few times.
+    fn last_before<'a>(
+        &'a self,
+        contents: &Buffer,
+        regex: &Regex,
+        lines: Range<usize>,
+        before: usize,
+    ) -> Option<regex::Match<'a>> {
+        let line_start = |line: usize| contents.char_to_byte(contents.line_to_char(line));
+        let (mut end, mut size) = (lines.end, 1);
+        while end > lines.start {
+            let start = end.saturating_sub(size).max(lines.start);
+            let block = line_start(start)..line_start(end).min(before);
+            if let Some(last) = self.last_in(regex, block) {
+                return Some(last);
+            }
+            end = start;
+            size *= 2;
+        }
+        None
+    }
+
+    /// The last match starting in the bytes `range`.
+    fn last_in<'a>(&'a self, regex: &Regex, range: Range<usize>) -> Option<regex::Match<'a>> {
+        let mut last = None;
+        let mut from = range.start;
+        while let Some(candidate) = regex.find_at(&self.text, from) {
+            if candidate.start() >= range.end {
+                break;
+            }
+            last = Some(candidate);
+            // An empty match would be found again, step over a char.
+            from = match self.text[candidate.end()..].chars().next() {
+                Some(char) if candidate.is_empty() => candidate.end() + char.len_utf8(),
+                None if candidate.is_empty() => break,
+                _ => candidate.end(),
+            };
+        }
+        last
+    }
+}
+
+/// The keyword under or after the cursor on its line, what `*` and `#`
+/// search for, and where it starts.
+pub fn word_at(contents: &Buffer, index: usize) -> Option<(usize, String)> {
+    let is_keyword = |char: char| char.is_alphanumeric() || char == '_';
+    let line = contents.char_to_line(index);
+    let line_start = contents.line_to_char(line);
+    let chars: Vec<char> = contents.line(line)?.chars().collect();
+    let column = index - line_start;
+    let start = column
+        + chars
+            .get(column..)?
+            .iter()
+            .position(|&char| is_keyword(char))?;
+    let start = chars[..start]
+        .iter()
+        .rposition(|&char| !is_keyword(char))
+        .map_or(0, |before| before + 1);
+    let end = chars[start..]
+        .iter()
+        .position(|&char| !is_keyword(char))
+        .map_or(chars.len(), |after| start + after);
+    Some((line_start + start, chars[start..end].iter().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn find_all(text: &str, pattern: &str, index: usize, backward: bool) -> Option<Found> {
+        let contents = Buffer::from(text);
+        let regex = regex(pattern, &Options::default(), true).unwrap();
+        Haystack::new(&contents).find(&contents, &regex, index, backward, true)
+    }
+
+    #[test]
+    fn forward_goes_to_the_next_match_and_wraps() {
+        let text = "foo bar\nbar foo\n";
+        assert_eq!(find_all(text, "foo", 0, false).unwrap().range, 12..15);
+        let wrapped = find_all(text, "foo", 12, false).unwrap();
+        assert_eq!(wrapped.range, 0..3);
+        assert!(wrapped.wrapped);
+    }
+
+    #[test]
+    fn backward_goes_to_the_closest_match_before() {
+        let text = "foo foo\nbar foo\nbaz\n";
+        assert_eq!(find_all(text, "foo", 18, true).unwrap().range, 12..15);
+        assert_eq!(find_all(text, "foo", 12, true).unwrap().range, 4..7);
+        assert_eq!(find_all(text, "foo", 4, true).unwrap().range, 0..3);
+        let wrapped = find_all(text, "foo", 0, true).unwrap();
+        assert_eq!(wrapped.range, 12..15);
+        assert!(wrapped.wrapped);
+    }
+
+    #[test]
+    fn backward_finds_matches_across_lines() {
+        let text = "foo\nbar\nfoo\nbaz\n";
+        assert_eq!(find_all(text, "o\\nb", 14, true).unwrap().range, 10..13);
+        assert_eq!(find_all(text, "o\\nb", 10, true).unwrap().range, 2..5);
+        assert_eq!(find_all(text, "^", 8, true).unwrap().range, 4..4);
+    }
+
+    #[test]
+    fn backward_search_through_a_long_buffer_is_not_quadratic() {
+        let mut text = String::from("needle\n");
+        for line in 0..20_000 {
+            text.push_str(&format!("line {line} of hay\n"));
+        }
+        let start = Instant::now();
+        let found = find_all(&text, "needle", text.chars().count() - 1, true).unwrap();
+        assert_eq!(found.range, 0..6);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn smartcase_only_applies_with_ignorecase() {
+        let options = Options::default();
+        assert!(regex("foo", &options, true).unwrap().is_match("FOO"));
+        assert!(!regex("Foo", &options, true).unwrap().is_match("FOO"));
+        assert!(regex("Foo", &options, false).unwrap().is_match("FOO"));
+    }
+
+    #[test]
+    fn word_at_finds_the_keyword_under_or_after_the_cursor() {
+        let contents = Buffer::from("  foo_bar(baz)\n");
+        assert_eq!(word_at(&contents, 0), Some((2, "foo_bar".to_string())));
+        assert_eq!(word_at(&contents, 5), Some((2, "foo_bar".to_string())));
+        assert_eq!(word_at(&contents, 9), Some((10, "baz".to_string())));
+    }
+}
@@ -0,0 +1,110 @@
+use std::ops::Range;
+
+use crate::{cursor::Cursor, file::JimFile};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionKind {
+    Characterwise,
+    Linewise,
+    Blockwise,
+}
+
+/// The text between two cursors, with both ends inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub kind: SelectionKind,
+    pub start: usize,
+    pub end: usize,
+    pub first_line: usize,
+    pub last_line: usize,
+    pub left_column: usize,
+    pub right_column: usize,
+}
+
+impl Selection {
+    pub fn new(kind: SelectionKind, anchor: &Cursor, cursor: &Cursor, file: &JimFile) -> Self {
+        let anchor_position = anchor.get_position(file);
+        let cursor_position = cursor.get_position(file);
+        Self {
+            kind,
+            start: anchor.index.min(cursor.index),
+            end: anchor.index.max(cursor.index),
+            first_line: anchor_position.y.min(cursor_position.y),
+            last_line: anchor_position.y.max(cursor_position.y),
+            left_column: anchor_position.x.min(cursor_position.x),
+            right_column: anchor_position.x.max(cursor_position.x),
+        }
+    }
+
+    /// A characterwise selection of the non-empty `range`.
+    pub fn characterwise(range: Range<usize>, file: &JimFile) -> Self {
+        let contents = &file.contents;
+        let end = range.end - 1;
+        let first_line = contents.char_to_line(range.start);
+        let last_line = contents.char_to_line(end);
+        Self {
+            kind: SelectionKind::Characterwise,
+            start: range.start,
+            end,
+            first_line,
+            last_line,
+            left_column: range.start - contents.line_to_char(first_line),
+            right_column: end - contents.line_to_char(last_line),
+        }
+    }
+
+    /// A linewise selection of the lines `first..=last`.
+    pub fn lines(first: usize, last: usize, file: &JimFile) -> Self {
+        let contents = &file.contents;
+        Self {
+            kind: SelectionKind::Linewise,
+            start: contents.line_to_char(first),
+            end: contents.line_to_char(last),
+            first_line: first,
+            last_line: last,
+            left_column: 0,
+            right_column: 0,
+        }
+    }
+
+    /// The char ranges covered, one per line for a block and one otherwise.
+    pub fn ranges(&self, file: &JimFile) -> Vec<Range<usize>> {
+        let contents = &file.contents;
+        let range = match self.kind {
+            SelectionKind::Characterwise => self.start..(self.end + 1).min(contents.len_chars()),
+            SelectionKind::Linewise => {
+                contents.line_to_char(self.first_line)..contents.line_to_char(self.last_line + 1)
+            }
+            SelectionKind::Blockwise => {
+                return (self.first_line..=self.last_line)
+                    .filter_map(|line| {
+                        let columns = self.columns_on_line(line, file)?;
+                        let line_start = contents.line_to_char(line);
+                        Some(line_start + columns.start..line_start + columns.end)
+                    })
+                    .collect();
+            }
+        };
+        vec![range]
+    }
+
+    /// The columns of `line` that are selected, if any.
+    pub fn columns_on_line(&self, line: usize, file: &JimFile) -> Option<Range<usize>> {
+        if line < self.first_line || line > self.last_line {
+            return None;
+        }
+        let length = file.contents.line_len(line)?;
+        match self.kind {
+            SelectionKind::Characterwise => {
+                let line_start = file.contents.line_to_char(line);
+                let start = self.start.saturating_sub(line_start).min(length);
+                let end = (self.end + 1).saturating_sub(line_start).min(length);
+                Some(start..end)
+            }
+            SelectionKind::Linewise => Some(0..length),
+            SelectionKind::Blockwise => {
+                Some(self.left_column.min(length)..(self.right_column + 1).min(length))
+            }
+        }
+    }
+}
@@ -0,0 +1,348 @@
+use std::ops::Range;
+
+use regex::{Captures, Regex, RegexBuilder};
+
+use crate::{
+    ex::{self, split_at_delimiter},
+    file::JimFile,
+    options::Options,
+    search,
+};
+
+/// A `:s/pattern/replacement/flags`. The pattern is a Rust regex, the
+/// replacement can refer to groups with `\1` and to the whole match with
+/// `&`, and `\r` in it breaks the line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Substitute {
+    pub pattern: String,
+    pub replacement: String,
+    pub flags: Flags,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Flags {
+    /// `g`, every match on a line instead of the first.
+    pub global: bool,
+    /// `i` ignores case and `I` doesn't, without either 'ignorecase' and
+    /// 'smartcase' decide like they do for `/`.
+    pub ignore_case: Option<bool>,
+    /// `c`, asks before each substitution.
+    pub confirm: bool,
+}
+
+impl Substitute {
+    /// Reads `/pattern/replacement/flags`, the last parts can be left off.
+    pub fn parse(arguments: &str) -> Result<Self, String> {
+        let (delimiter, pattern, rest) = ex::parse_pattern(arguments)?;
+        let (replacement, flags) = match rest {
+            Some(rest) => split_at_delimiter(rest, delimiter),
+            None => (String::new(), None),
+        };
+        let mut substitute = Self {
+            pattern,
+            replacement,
+            flags: Flags::default(),
+        };
+        substitute.flags.apply(flags.unwrap_or_default().trim())?;
+        Ok(substitute)
+    }
+
+    pub fn regex(&self, options: &Options) -> Result<Regex, String> {
+        match self.flags.ignore_case {
+            Some(ignore_case) => RegexBuilder::new(&self.pattern)
+                .case_insensitive(ignore_case)
+                .build()
+                .map_err(|error| error.to_string()),
+            None => search::regex(&self.pattern, options, true),
+        }
+    }
+}
+
+impl Flags {
+    /// Turns on the flags in `flags`, `&` first keeps the ones already set.
+    pub fn apply(&mut self, flags: &str) -> Result<(), String> {
+        let flags = match flags.strip_prefix('&') {
+            Some(flags) => flags,
+            None => {
+                *self = Self::default();
+                flags
+            }
+        };
+        for flag in flags.chars() {
+            match flag {
+                'g' => self.global = true,
+                'i' => self.ignore_case = Some(true),
+                'I' => self.ignore_case = Some(false),
+                'c' => self.confirm = true,
+                _ => return Err(format!("Trailing characters: {flags}")),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fills in `replacement` for one match.
+fn expand(replacement: &str, captures: &Captures) -> String {
+    let group = |index: usize| captures.get(index).map_or("", |group| group.as_str());
+    let mut expanded = String::new();
+    let mut chars = replacement.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            '&' => expanded.push_str(group(0)),
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => {
+                    expanded.push_str(group(digit.to_digit(10).unwrap_or(0) as usize));
+                }
+                Some('r' | 'n') => expanded.push('\n'),
+                Some('t') => expanded.push('\t'),
+                Some(other) => expanded.push(other),
+                None => expanded.push('\\'),
+            },
+            _ => expanded.push(char),
+        }
+    }
+    expanded
+}
+
+/// A substitution going through the lines of a range, which may stop to
+/// ask about each match.
+#[derive(Debug)]
+pub struct Substitution {
+    regex: Regex,
+    replacement: String,
+    flags: Flags,
+    line: usize,
+    /// Byte offset in `line` to look for the next match from.
+    column: usize,
+    last_line: usize,
+    pub count: usize,
+    pub changed_lines: usize,
+    last_changed: Option<usize>,
+    /// Anything matched, even if it was skipped.
+    pub matched: bool,
+    /// The match waiting for an answer with the `c` flag.
+    pub pending: Option<Match>,
+}
+
+/// A match waiting to be replaced or skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// Where the match is in the buffer, in chars.
+    pub range: Range<usize>,
+    pub replacement: String,
+    line: usize,
+    /// The match in bytes of its line.
+    columns: Range<usize>,
+}
+
+impl Substitution {
+    pub fn new(
+        substitute: &Substitute,
+        options: &Options,
+        first_line: usize,
+        last_line: usize,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            regex: substitute.regex(options)?,
+            replacement: substitute.replacement.clone(),
+            flags: substitute.flags,
+            line: first_line,
+            column: 0,
+            last_line,
+            count: 0,
+            changed_lines: 0,
+            last_changed: None,
+            matched: false,
+            pending: None,
+        })
+    }
+
+    pub fn confirm(&self) -> bool {
+        self.flags.confirm
+    }
+
+    /// Stops asking, the rest is replaced without confirmation.
+    pub fn stop_confirming(&mut self) {
+        self.flags.confirm = false;
+    }
+
+    /// The line of the last substitution.
+    pub fn last_changed(&self) -> Option<usize> {
+        self.last_changed
+    }
+
+    /// Finds the next match in the range, moving on to later lines.
+    pub fn next_match(&mut self, file: &JimFile) -> Option<Match> {
+        let contents = &file.contents;
+        while self.line <= self.last_line && self.line < contents.len_lines() {
+            let text = contents.line(self.line)?.to_string();
+            let text = text.trim_end_matches(['\n', '\r']);
+            let found = match self.column <= text.len() {
+                true => self.regex.captures_at(text, self.column),
+                false => None,
+            };
+            let Some(captures) = found else {
+                self.next_line();
+                continue;
+            };
+            self.matched = true;
+            let whole = captures.get(0)?;
+            let line_start = contents.line_to_char(self.line);
+            let start = line_start + text[..whole.start()].chars().count();
+            return Some(Match {
+                range: start..start + whole.as_str().chars().count(),
+                replacement: expand(&self.replacement, &captures),
+                line: self.line,
+                columns: whole.range(),
+            });
+        }
+        None
+    }
+
+    /// Replaces `found`, the last match `next_match` returned.
+    pub fn replace(&mut self, file: &mut JimFile, found: &Match) {
+        if !found.range.is_empty() {
+            file.remove(found.range.clone());
+        }
+        if !found.replacement.is_empty() {
+            file.insert(found.range.start, &found.replacement);
+        }
+        self.count += 1;
+        if self.last_changed != Some(found.line) {
+            self.changed_lines += 1;
+        }
+
+        // Line breaks in the replacement push the rest of the range down.
+        let breaks = found.replacement.matches('\n').count();
+        self.last_line += breaks;
+        self.line = found.line + breaks;
+        self.column = match found.replacement.rfind('\n') {
+            Some(index) => found.replacement.len() - index - 1,
+            None => found.columns.start + found.replacement.len(),
+        };
+        self.last_changed = Some(self.line);
+        self.advance(found.columns.is_empty(), file);
+    }
+
+    /// Leaves `found` as it is.
+    pub fn skip(&mut self, found: &Match, file: &JimFile) {
+        self.column = found.columns.end;
+        self.advance(found.columns.is_empty(), file);
+    }
+
+    /// Moves past a match, to the next line unless every match on a line
+    /// is wanted. An empty match has to step over a character so it isn't
+    /// found again.
+    fn advance(&mut self, empty: bool, file: &JimFile) {
+        if !self.flags.global {
+            self.next_line();
+            return;
+        }
+        if empty {
+            let text = file
+                .contents
+                .line(self.line)
+                .map(|line| line.to_string())
+                .unwrap_or_default();
+            match text[self.column.min(text.len())..].chars().next() {
+                Some(char) if char != '\n' => self.column += char.len_utf8(),
+                _ => self.next_line(),
+            }
+        }
+    }
+
+    fn next_line(&mut self) {
+        self.line += 1;
+        self.column = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `:%s` with `arguments` over `text` without confirmation.
+    fn substitute(text: &str, arguments: &str, options: &Options) -> String {
+        let mut file = JimFile::scratch("test", text);
+        let substitute = Substitute::parse(arguments).unwrap();
+        let last_line = file.contents.len_lines().saturating_sub(1);
+        let mut substitution = Substitution::new(&substitute, options, 0, last_line).unwrap();
+        while let Some(found) = substitution.next_match(&file) {
+            substitution.replace(&mut file, &found);
+        }
+        file.contents.to_string()
+    }
+
+    #[test]
+    fn parse_reads_pattern_replacement_and_flags() {
+        let substitute = Substitute::parse("/a\\/b/c/gc").unwrap();
+        assert_eq!(substitute.pattern, "a/b");
+        assert_eq!(substitute.replacement, "c");
+        assert!(substitute.flags.global);
+        assert!(substitute.flags.confirm);
+        assert_eq!(substitute.flags.ignore_case, None);
+
+        let substitute = Substitute::parse("#x#").unwrap();
+        assert_eq!(substitute.pattern, "x");
+        assert_eq!(substitute.replacement, "");
+        assert_eq!(substitute.flags, Flags::default());
+
+        assert_eq!(Substitute::parse("/x").unwrap().replacement, "");
+        assert!(Substitute::parse("/x/y/q").is_err());
+        assert!(Substitute::parse("xay").is_err());
+    }
+
+    #[test]
+    fn ampersand_keeps_the_flags_already_set() {
+        let mut flags = Flags::default();
+        flags.apply("gi").unwrap();
+        flags.apply("&c").unwrap();
+        assert!(flags.global && flags.confirm);
+        assert_eq!(flags.ignore_case, Some(true));
+
+        flags.apply("I").unwrap();
+        assert!(!flags.global && !flags.confirm);
+        assert_eq!(flags.ignore_case, Some(false));
+    }
+
+    #[test]
+    fn case_flags_override_the_options() {
+        let options = Options::default();
+        assert_eq!(substitute("Ab\n", "/a/x/", &options), "xb\n");
+        assert_eq!(substitute("Ab\n", "/a/x/I", &options), "Ab\n");
+        assert_eq!(substitute("ab\n", "/A/x/", &options), "ab\n");
+        assert_eq!(substitute("ab\n", "/A/x/i", &options), "xb\n");
+
+        let options = Options {
+            ignorecase: false,
+            ..Options::default()
+        };
+        assert_eq!(substitute("Ab\n", "/a/x/", &options), "Ab\n");
+        assert_eq!(substitute("Ab\n", "/a/x/i", &options), "xb\n");
+    }
+
+    #[test]
+    fn replacements_expand_groups_and_line_breaks() {
+        let options = Options::default();
+        assert_eq!(substitute("ab ab\n", "/b/[&]/", &options), "a[b] ab\n");
+        assert_eq!(
+            substitute("ab ab\n", "/(a)(b)/\\2\\1/g", &options),
+            "ba ba\n"
+        );
+        assert_eq!(substitute("a&b\n", "/&/\\&/", &options), "a&b\n");
+        assert_eq!(
+            substitute("a,b\nc,d\n", "/,/\\r/", &options),
+            "a\nb\nc\nd\n"
+        );
+        assert_eq!(substitute("a\tb\n", "/\\t/\\\\/", &options), "a\\b\n");
+    }
+
+    #[test]
+    fn global_replaces_every_match_and_steps_over_empty_ones() {
+        let options = Options::default();
+        assert_eq!(substitute("aaa\n", "/a/b/", &options), "baa\n");
+        assert_eq!(substitute("aaa\n", "/a/b/g", &options), "bbb\n");
+        assert_eq!(substitute("ab\n", "/x*/-/g", &options), "-a-b-\n");
+        assert_eq!(substitute("a,b,c\n", "/,/\\r/g", &options), "a\nb\nc\n");
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Tells when files that buffers show are changed by something else.
+/// Directories are watched instead of the files themselves, since tools
+/// that save by renaming over a file (jim included) replace the file that
+/// was being watched.
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+    /// Paths as buffers name them, so they're only resolved once.
+    requested: HashSet<PathBuf>,
+    files: HashSet<PathBuf>,
+    directories: HashSet<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn new() -> notify::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        Ok(Self {
+            watcher: notify::recommended_watcher(sender)?,
+            events,
+            requested: HashSet::new(),
+            files: HashSet::new(),
+            directories: HashSet::new(),
+        })
+    }
+
+    /// Starts watching `path` if it isn't watched yet. Files that can't be
+    /// watched are left to `:checktime`.
+    pub fn watch(&mut self, path: &Path) {
+        if self.requested.contains(path) {
+            return;
+        }
+        let Ok(canonical) = path.canonicalize() else {
+            return;
+        };
+        let Some(directory) = canonical.parent() else {
+            return;
+        };
+        if !self.directories.contains(directory)
+            && self
+                .watcher
+                .watch(directory, RecursiveMode::NonRecursive)
+                .is_ok()
+        {
+            self.directories.insert(directory.to_path_buf());
+        }
+        self.files.insert(canonical);
+        self.requested.insert(path.to_path_buf());
+    }
+
+    /// Whether any watched file changed since the last call. Never waits.
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok(event) = self.events.try_recv() {
+            let Ok(event) = event else {
+                continue;
+            };
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+            changed |= event.paths.iter().any(|path| self.files.contains(path));
+        }
+        changed
+    }
+}
+
+impl std::fmt::Debug for FileWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileWatcher")
+            .field("files", &self.files)
+            .finish()
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+
+use nalgebra::Vector2;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+
+use crate::{buffer::Buffer, cursor::Cursor, options::Options};
+
+/// A view into one of the buffers. The current window's buffer and cursor
+/// live in `JimProperties`, its entry here is only brought up to date when
+/// another window gets focused.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Window {
+    pub buffer: usize,
+    pub cursor: Cursor,
+    pub top_line: usize,
+    pub left_column: usize,
+    /// Highlight the lines that differ from the other diff windows.
+    pub diff: bool,
+}
+
+impl Window {
+    /// Scrolls just enough to show `position` in a view of `size` with
+    /// `scrolloff` lines and `sidescrolloff` columns around it, or fewer
+    /// where the text ends first.
+    pub fn scroll_to(
+        &mut self,
+        position: Vector2<usize>,
+        contents: &Buffer,
+        size: Vector2<usize>,
+        options: &Options,
+    ) {
+        let margin = options.scrolloff.min(size.y.saturating_sub(1) / 2);
+        let below = margin.min(contents.len_lines().saturating_sub(position.y + 1));
+        if position.y + below >= self.top_line + size.y {
+            self.top_line = (position.y + below + 1).saturating_sub(size.y);
+        }
+        if position.y < self.top_line + margin {
+            self.top_line = position.y.saturating_sub(margin);
+        }
+
+        let margin = options.sidescrolloff.min(size.x.saturating_sub(1) / 2);
+        let line_length = contents.line_len(position.y).unwrap_or_default();
+        let right = margin.min(line_length.saturating_sub(position.x + 1));
+        if position.x + right >= self.left_column + size.x {
+            self.left_column = (position.x + right + 1).saturating_sub(size.x);
+        }
+        if position.x < self.left_column + margin {
+            self.left_column = position.x.saturating_sub(margin);
+        }
+    }
+}
+
+/// Where `zt`, `zz` and `zb` put the cursor line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenLine {
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// The part of a window's area the text goes in, above the status line.
+pub fn text_area(area: Rect) -> Rect {
+    Rect::new(area.x, area.y, area.width, area.height.saturating_sub(1))
+}
+
+/// How a window is split, named like vim does: `:split` is horizontal and
+/// stacks the windows, `:vsplit` puts them side by side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split {
+    Horizontal,
+    Vertical,
+}
+
+impl Split {
+    fn direction(self) -> Direction {
+        match self {
+            Split::Horizontal => Direction::Vertical,
+            Split::Vertical => Direction::Horizontal,
+        }
+    }
+
+    fn size(self, area: Rect) -> u16 {
+        match self {
+            Split::Horizontal => area.height,
+            Split::Vertical => area.width,
+        }
+    }
+}
+
+/// Where to look for a window from the current one, as with `<C-w>h`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Below,
+    Above,
+    Right,
+}
+
+#[derive(Debug)]
+enum Node {
+    Window(usize),
+    /// Children with their share of the space.
+    Split {
+        split: Split,
+        children: Vec<(Node, u32)>,
+    },
+}
+
+impl Node {
+    /// Child indices leading from this node to window `id`.
+    fn path_to(&self, id: usize) -> Option<Vec<usize>> {
+        match self {
+            Node::Window(window) => (*window == id).then(Vec::new),
+            Node::Split { children, .. } => {
+                children.iter().enumerate().find_map(|(index, (child, _))| {
+                    let mut path = child.path_to(id)?;
+                    path.insert(0, index);
+                    Some(path)
+                })
+            }
+        }
+    }
+
+    fn node_at(&self, path: &[usize]) -> &Node {
+        match (self, path.split_first()) {
+            (Node::Split { children, .. }, Some((&index, rest))) => children[index].0.node_at(rest),
+            _ => self,
+        }
+    }
+
+    fn node_at_mut(&mut self, path: &[usize]) -> &mut Node {
+        match (self, path.split_first()) {
+            (Node::Split { children, .. }, Some((&index, rest))) => {
+                children[index].0.node_at_mut(rest)
+            }
+            (node, _) => node,
+        }
+    }
+
+    fn first_window(&self) -> usize {
+        match self {
+            Node::Window(id) => *id,
+            Node::Split { children, .. } => children[0].0.first_window(),
+        }
+    }
+
+    fn last_window(&self) -> usize {
+        match self {
+            Node::Window(id) => *id,
+            Node::Split { children, .. } => children[children.len() - 1].0.last_window(),
+        }
+    }
+
+    /// The areas the children of a split get, without the separators.
+    fn child_areas(&self, area: Rect) -> Vec<Rect> {
+        let Node::Split { split, children } = self else {
+            return vec![area];
+        };
+        let total = children.iter().map(|(_, weight)| weight).sum();
+        let constraints: Vec<Constraint> = children
+            .iter()
+            .map(|(_, weight)| Constraint::Ratio(*weight, total))
+            .collect();
+        Layout::default()
+            .direction(split.direction())
+            .constraints(constraints)
+            .split(area)
+    }
+
+    fn layout(&self, area: Rect, areas: &mut Vec<(usize, Rect)>) {
+        match self {
+            Node::Window(id) => areas.push((*id, area)),
+            Node::Split { split, children } => {
+                let child_areas = self.child_areas(area);
+                for (index, ((child, _), mut area)) in children.iter().zip(child_areas).enumerate()
+                {
+                    // Side by side windows leave a column for the separator.
+                    if *split == Split::Vertical && index + 1 < children.len() {
+                        area.width = area.width.saturating_sub(1);
+                    }
+                    child.layout(area, areas);
+                }
+            }
+        }
+    }
+}
+
+/// The window tree. Windows are identified by an id that stays the same
+/// while the tree around them changes.
+#[derive(Debug)]
+pub struct Windows {
+    windows: HashMap<usize, Window>,
+    layout: Node,
+    current: usize,
+    next_id: usize,
+    /// Where the windows were last drawn, used to find neighbours.
+    pub area: Rect,
+}
+
+impl Default for Windows {
+    fn default() -> Self {
+        Self {
+            windows: HashMap::from([(0, Window::default())]),
+            layout: Node::Window(0),
+            current: 0,
+            next_id: 1,
+            area: Rect::new(0, 0, 80, 24),
+        }
+    }
+}
+
+impl Windows {
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Window> {
+        self.windows.get(&id)
+    }
+
+    pub fn current_window(&self) -> &Window {
+        &self.windows[&self.current]
+    }
+
+    pub fn current_window_mut(&mut self) -> &mut Window {
+        self.windows.get_mut(&self.current).unwrap()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Window> {
+        self.windows.values()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Window> {
+        self.windows.values_mut()
+    }
+
+    pub fn focus(&mut self, id: usize) {
+        if self.windows.contains_key(&id) {
+            self.current = id;
+        }
+    }
+
+    /// Splits the current window in two, the new half shows the same thing
+    /// and gets focused. It goes above or to the left like in vim.
+    pub fn split(&mut self, split: Split) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.windows.insert(id, *self.current_window());
+
+        let path = self.layout.path_to(self.current).unwrap();
+        if let Some((&index, parent_path)) = path.split_last() {
+            if let Node::Split {
+                split: parent_split,
+                children,
+            } = self.layout.node_at_mut(parent_path)
+            {
+                if *parent_split == split {
+                    for (_, weight) in children.iter_mut() {
+                        *weight *= 2;
+                    }
+                    children[index].1 /= 2;
+                    let weight = children[index].1;
+                    children.insert(index, (Node::Window(id), weight));
+                    self.current = id;
+                    return;
+                }
+            }
+        }
+
+        let node = self.layout.node_at_mut(&path);
+        *node = Node::Split {
+            split,
+            children: vec![(Node::Window(id), 1), (Node::Window(self.current), 1)],
+        };
+        self.current = id;
+    }
+
+    /// Closes the current window, its space goes to the window after it.
+    /// The last window can't be closed.
+    pub fn close(&mut self) -> bool {
+        if self.windows.len() == 1 {
+            return false;
+        }
+        let path = self.layout.path_to(self.current).unwrap();
+        let (&index, parent_path) = path.split_last().unwrap();
+        let parent = self.layout.node_at_mut(parent_path);
+        let Node::Split { children, .. } = parent else {
+            return false;
+        };
+
+        let (_, weight) = children.remove(index);
+        let next = if index < children.len() {
+            children[index].1 += weight;
+            children[index].0.first_window()
+        } else {
+            children[index - 1].1 += weight;
+            children[index - 1].0.last_window()
+        };
+        if children.len() == 1 {
+            let (only, _) = children.remove(0);
+            *parent = only;
+        }
+
+        self.windows.remove(&self.current);
+        self.current = next;
+        true
+    }
+
+    /// Closes every window but the current one.
+    pub fn only(&mut self) {
+        self.windows.retain(|&id, _| id == self.current);
+        self.layout = Node::Window(self.current);
+    }
+
+    /// Where the text of window `id` was last drawn.
+    pub fn text_area(&self, id: usize) -> Rect {
+        self.areas(self.area)
+            .into_iter()
+            .find(|&(window, _)| window == id)
+            .map_or_else(Rect::default, |(_, area)| text_area(area))
+    }
+
+    /// The area each window is drawn in, status line included.
+    pub fn areas(&self, area: Rect) -> Vec<(usize, Rect)> {
+        let mut areas = vec![];
+        self.layout.layout(area, &mut areas);
+        areas
+    }
+
+    /// The window after the current one, wrapping around like `<C-w>w`.
+    pub fn next(&self) -> usize {
+        let areas = self.areas(self.area);
+        let index = areas
+            .iter()
+            .position(|&(id, _)| id == self.current)
+            .unwrap_or_default();
+        areas[(index + 1) % areas.len()].0
+    }
+
+    /// The nearest window on `side` of the current one, `None` at the edge.
+    pub fn neighbour(&self, side: Side) -> Option<usize> {
+        let areas = self.areas(self.area);
+        let (_, current) = *areas.iter().find(|&&(id, _)| id == self.current)?;
+        let overlaps_rows = |area: &Rect| area.y < current.bottom() && current.y < area.bottom();
+        let overlaps_columns = |area: &Rect| area.x < current.right() && current.x < area.right();
+
+        areas
+            .iter()
+            .filter_map(|&(id, area)| {
+                let distance = match side {
+                    Side::Left if overlaps_rows(&area) && area.right() <= current.x => {
+                        current.x - area.right()
+                    }
+                    Side::Right if overlaps_rows(&area) && current.right() <= area.x => {
+                        area.x - current.right()
+                    }
+                    Side::Above if overlaps_columns(&area) && area.bottom() <= current.y => {
+                        current.y - area.bottom()
+                    }
+                    Side::Below if overlaps_columns(&area) && current.bottom() <= area.y => {
+                        area.y - current.bottom()
+                    }
+                    _ => return None,
+                };
+                // Prefer the window lined up with the top left of this one.
+                let offset = match side {
+                    Side::Left | Side::Right => area.y.abs_diff(current.y),
+                    Side::Above | Side::Below => area.x.abs_diff(current.x),
+                };
+                Some((distance, offset, id))
+            })
+            .min()
+            .map(|(_, _, id)| id)
+    }
+
+    /// Grows the current window by `amount` rows or columns, or shrinks it
+    /// if `amount` is negative, taking the space from the windows next to
+    /// it in the closest split going the right way.
+    pub fn resize(&mut self, split: Split, amount: isize) {
+        let path = self.layout.path_to(self.current).unwrap();
+        let Some(depth) = (0..path.len()).rev().find(|&depth| {
+            matches!(self.layout.node_at(&path[..depth]), Node::Split { split: found, .. } if *found == split)
+        }) else {
+            return;
+        };
+
+        let mut area = self.area;
+        for level in 0..depth {
+            area = self.layout.node_at(&path[..level]).child_areas(area)[path[level]];
+        }
+        let node = self.layout.node_at_mut(&path[..depth]);
+        let mut sizes: Vec<isize> = node
+            .child_areas(area)
+            .into_iter()
+            .map(|area| split.size(area) as isize)
+            .collect();
+
+        let index = path[depth];
+        let others = (0..sizes.len()).filter(|&other| other != index);
+        let most = sizes.iter().sum::<isize>() - others.count() as isize;
+        let mut remaining = (sizes[index] + amount).clamp(1, most.max(1)) - sizes[index];
+        let neighbours = (index + 1..sizes.len()).chain((0..index).rev());
+        for neighbour in neighbours {
+            let change = if remaining > 0 {
+                remaining.min(sizes[neighbour] - 1)
+            } else {
+                remaining
+            };
+            sizes[neighbour] -= change;
+            sizes[index] += change;
+            remaining -= change;
+            if remaining == 0 {
+                break;
+            }
+        }
+
+        if let Node::Split { children, .. } = node {
+            for ((_, weight), size) in children.iter_mut().zip(sizes) {
+                *weight = size.max(1) as u32;
+            }
+        }
+    }
+
+    /// Gives every window the same share of its split, like `<C-w>=`.
+    pub fn equalize(&mut self) {
+        fn equalize(node: &mut Node) {
+            if let Node::Split { children, .. } = node {
+                for (child, weight) in children {
+                    *weight = 1;
+                    equalize(child);
+                }
+            }
+        }
+        equalize(&mut self.layout);
+    }
+}